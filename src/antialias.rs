@@ -0,0 +1,189 @@
+use crate::color::Color;
+use crate::image::Image;
+
+/// How the samples of a supersampled image are combined into pixels
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+    /// averages the samples inside each pixel
+    Box,
+    /// weighs samples by how close they are to the middle of the pixel,
+    /// reaching into the pixels around it, which gives softer edges
+    Tent,
+}
+
+impl Filter {
+    /// the filter with the given name, as written after antialias
+    pub fn from_name(name: &str) -> Option<Filter> {
+        match name {
+            "box" => Some(Filter::Box),
+            "tent" => Some(Filter::Tent),
+            _ => None,
+        }
+    }
+}
+
+/// Supersampling: every frame is drawn factor times as wide and as tall,
+/// then filtered down to the screen size
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Antialias {
+    pub factor: usize,
+    pub filter: Filter,
+}
+
+impl Antialias {
+    /// the largest factor, since the samples of a pixel grow with its square
+    pub const MAX_FACTOR: usize = 8;
+
+    pub fn new(factor: usize, filter: Filter) -> Antialias {
+        Antialias { factor, filter }
+    }
+
+    /// no antialiasing, with one sample for each pixel
+    pub fn none() -> Antialias {
+        Antialias::new(1, Filter::Box)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.factor > 1
+    }
+}
+
+impl Default for Antialias {
+    fn default() -> Antialias {
+        Antialias::none()
+    }
+}
+
+impl Image {
+    /// Inputs:   antialias: how the image was supersampled
+    ///
+    /// Returns: the image filtered down to 1 / antialias.factor of its width
+    /// and height. Each pixel of the z buffer is the closest of its samples
+    pub fn downsample(&self, antialias: &Antialias) -> Image {
+        let factor = antialias.factor.max(1);
+        let width = self.width / factor;
+        let height = self.height / factor;
+        let mut image = Image::new(width, height);
+        let column_weights = sample_weights(width, self.width, factor, antialias.filter);
+        let row_weights = sample_weights(height, self.height, factor, antialias.filter);
+        for (y, rows) in row_weights.iter().enumerate() {
+            for (x, columns) in column_weights.iter().enumerate() {
+                let mut sum = [0.0; 3];
+                for &(row, row_weight) in rows.iter() {
+                    for &(column, column_weight) in columns.iter() {
+                        let sample = &self.screen[row][column];
+                        let weight = row_weight * column_weight;
+                        sum[0] += sample.r as f32 * weight;
+                        sum[1] += sample.g as f32 * weight;
+                        sum[2] += sample.b as f32 * weight;
+                    }
+                }
+                image.screen[y][x] = Color::new_color(sum[0].round() as u8, sum[1].round() as u8, sum[2].round() as u8);
+                // the z buffer is only kept for the samples inside the pixel
+                let mut z = f32::NEG_INFINITY;
+                for row in y * factor..(y + 1) * factor {
+                    for column in x * factor..(x + 1) * factor {
+                        z = z.max(self.z_buffer[row][column]);
+                    }
+                }
+                image.z_buffer[y][x] = z;
+            }
+        }
+        image
+    }
+}
+
+/// Inputs:   pixels: how many pixels wide or tall the filtered image is
+///
+/// samples: how many samples wide or tall the supersampled image is
+///
+/// Returns: for each pixel, the samples along one axis that go into it and
+/// how much each one counts, adding up to 1
+fn sample_weights(pixels: usize, samples: usize, factor: usize, filter: Filter) -> Vec<Vec<(usize, f32)>> {
+    let mut weights = Vec::with_capacity(pixels);
+    for pixel in 0..pixels {
+        let mut pixel_weights = Vec::new();
+        match filter {
+            Filter::Box => {
+                for sample in pixel * factor..(pixel + 1) * factor {
+                    pixel_weights.push((sample, 1.0));
+                }
+            }
+            Filter::Tent => {
+                // the tent is two pixels wide, peaking in the middle of this one
+                let middle = (pixel as f32 + 0.5) * factor as f32;
+                let first = (pixel * factor).saturating_sub(factor);
+                let last = ((pixel + 2) * factor).min(samples);
+                for sample in first..last {
+                    let distance = (sample as f32 + 0.5 - middle).abs() / factor as f32;
+                    if distance < 1.0 {
+                        pixel_weights.push((sample, 1.0 - distance));
+                    }
+                }
+            }
+        }
+        // samples past the edges of the image are left out, so the rest are
+        // scaled up to make up for them
+        let total: f32 = pixel_weights.iter().map(|(_, weight)| weight).sum();
+        for (_, weight) in pixel_weights.iter_mut() {
+            *weight /= total;
+        }
+        weights.push(pixel_weights);
+    }
+    weights
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: Color = Color::new_color(255, 255, 255);
+
+    /// a 4x4 supersampled image that is black except for one white sample
+    fn one_white_sample(row: usize, column: usize) -> Image {
+        let mut image = Image::new(4, 4);
+        image.screen[row][column] = WHITE;
+        image
+    }
+
+    #[test]
+    fn sample_weights_add_up_to_one() {
+        for filter in [Filter::Box, Filter::Tent] {
+            for pixel_weights in sample_weights(5, 15, 3, filter) {
+                let total: f32 = pixel_weights.iter().map(|(_, weight)| weight).sum();
+                assert!((total - 1.0).abs() < 1e-6, "{:?} {:?}", filter, pixel_weights);
+            }
+        }
+    }
+
+    #[test]
+    fn box_filter_averages_the_samples_inside_each_pixel() {
+        let image = one_white_sample(1, 1).downsample(&Antialias::new(2, Filter::Box));
+        assert_eq!((image.width, image.height), (2, 2));
+        // a quarter of 255, rounded
+        assert_eq!(image.screen[0][0], Color::new_color(64, 64, 64));
+        assert_eq!(image.screen[0][1], Color::new());
+        assert_eq!(image.screen[1][1], Color::new());
+    }
+
+    #[test]
+    fn tent_filter_reaches_into_the_next_pixel() {
+        let image = one_white_sample(1, 1).downsample(&Antialias::new(2, Filter::Tent));
+        assert!(image.screen[0][0].r > image.screen[0][1].r);
+        assert!(image.screen[0][1].r > 0);
+        assert!(image.screen[1][1].r > 0);
+    }
+
+    #[test]
+    fn downsampled_z_buffer_keeps_the_closest_sample() {
+        let mut image = Image::new(4, 4);
+        image.z_buffer[0][1] = -5.0;
+        image.z_buffer[1][0] = 2.0;
+        image.z_buffer[2][2] = 7.0;
+        let image = image.downsample(&Antialias::new(2, Filter::Tent));
+        assert_eq!(image.z_buffer[0][0], 2.0);
+        assert_eq!(image.z_buffer[1][1], 7.0);
+        // the tent filter does not spread depth into the next pixel
+        assert_eq!(image.z_buffer[0][1], f32::NEG_INFINITY);
+    }
+}
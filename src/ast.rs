@@ -0,0 +1,152 @@
+//! The commands of an MDL script, read once from the text so that every
+//! frame can be drawn without going back to the parser.
+
+use crate::antialias::Antialias;
+use crate::draw::ShadingType;
+use crate::easing::Easing;
+use crate::image::DepthTest;
+use crate::error::{Located, Span};
+use crate::lighting::{Constants, SpecularModel};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+#[derive(Clone, Debug)]
+pub enum Command {
+    Frames { count: usize },
+    Basename { name: String },
+    Size { width: usize, height: usize },
+    /// time between the frames of the gif in hundredths of a second
+    Delay { delay: u16 },
+    Loop { count: u16 },
+    Dither,
+    Antialias { antialias: Antialias },
+    Set { knob: String, value: f32 },
+    SetKnobs { value: f32 },
+    SaveKnobs { name: String },
+    Vary {
+        knob: String,
+        start_frame: usize,
+        end_frame: usize,
+        start_value: f32,
+        end_value: f32,
+        easing: Easing,
+    },
+    Tween {
+        start_frame: usize,
+        end_frame: usize,
+        start_knobs: String,
+        end_knobs: String,
+    },
+    /// the knobs scale the eye and the aim
+    Camera {
+        eye: [f32; 3],
+        aim: [f32; 3],
+        eye_knob: Option<String>,
+        aim_knob: Option<String>,
+    },
+    Focal { focal: f32 },
+    Constants { name: String, constants: Constants },
+    /// the knobs scale the color and the location
+    Light {
+        name: String,
+        color: [f32; 3],
+        location: [f32; 3],
+        color_knob: Option<String>,
+        location_knob: Option<String>,
+    },
+    Ambient { color: [f32; 3] },
+    Shading { shading: ShadingType },
+    Specular { model: SpecularModel },
+    DepthTest { test: DepthTest },
+    /// added to the depth of everything drawn after it in the frame
+    DepthBias { bias: f32 },
+    Push,
+    Pop,
+    Move { offset: [f32; 3], knob: Option<String> },
+    Scale { scale: [f32; 3], knob: Option<String> },
+    Rotate { axis: Axis, degrees: f32, knob: Option<String> },
+    SaveCoordSystem { name: String },
+    Sphere {
+        constants: Option<String>,
+        center: [f32; 3],
+        radius: f32,
+        coord_system: Option<String>,
+    },
+    /// corner is the upper-left-front corner of the box
+    Box {
+        constants: Option<String>,
+        corner: [f32; 3],
+        size: [f32; 3],
+        coord_system: Option<String>,
+    },
+    Torus {
+        constants: Option<String>,
+        center: [f32; 3],
+        minor_radius: f32,
+        major_radius: f32,
+        coord_system: Option<String>,
+    },
+    /// each end of a line can be in its own coordinate system
+    Line {
+        constants: Option<String>,
+        start: [f32; 3],
+        start_coord_system: Option<String>,
+        end: [f32; 3],
+        end_coord_system: Option<String>,
+    },
+    Mesh {
+        constants: Option<String>,
+        file_name: String,
+        coord_system: Option<String>,
+    },
+    Save { file_name: String },
+    Display,
+}
+
+impl Command {
+    /// the names of the knobs the command sets or reads
+    pub fn knobs(&self) -> Vec<&str> {
+        let knobs = match self {
+            Command::Set { knob, .. } | Command::Vary { knob, .. } => vec![Some(knob)],
+            Command::Camera { eye_knob, aim_knob, .. } => vec![eye_knob.as_ref(), aim_knob.as_ref()],
+            Command::Light { color_knob, location_knob, .. } => vec![color_knob.as_ref(), location_knob.as_ref()],
+            Command::Move { knob, .. } | Command::Scale { knob, .. } | Command::Rotate { knob, .. } => vec![knob.as_ref()],
+            _ => Vec::new(),
+        };
+        knobs.into_iter().flatten().map(|knob| knob.as_str()).collect()
+    }
+}
+
+/// A command along with where it came from in the script
+#[derive(Clone, Debug)]
+pub struct Statement {
+    pub command: Command,
+    /// None for commands that were not read from a script
+    pub span: Option<Span>,
+    pub source: String,
+}
+
+impl Statement {
+    pub fn new(command: Command) -> Statement {
+        Statement {
+            command,
+            span: None,
+            source: String::new(),
+        }
+    }
+}
+
+impl Located for Statement {
+    fn span(&self) -> Option<Span> {
+        self.span
+    }
+
+    fn source(&self) -> &str {
+        &self.source
+    }
+}
@@ -0,0 +1,179 @@
+use crate::consts;
+use crate::gmath::{normalize, vector_subtraction};
+use crate::matrix::Matrix;
+
+/// Where the scene is viewed from.
+///
+/// The default camera is orthographic: points are already in screen
+/// coordinates and are drawn as they are. `camera` switches to a
+/// perspective projection looking from eye towards aim, with the focal
+/// length set by `focal` (or the distance from eye to aim, so that
+/// shapes at the aim point keep their size).
+///
+/// Either way the scene is laid out for a screen of scene_size, and is
+/// scaled to fit the actual screen it is drawn on.
+#[derive(Clone, Debug)]
+pub struct Camera{
+    pub eye: Vec<f32>,
+    pub aim: Vec<f32>,
+    pub focal: Option<f32>,
+    pub perspective: bool,
+    pub scene_size: [f32; 2],
+}
+
+impl Default for Camera {
+    fn default() -> Camera {
+        Camera::new()
+    }
+}
+
+impl Camera{
+    pub fn new() -> Camera{
+        Camera{eye: consts::VIEW.to_vec(), aim: vec![0.0; 3], focal: None, perspective: false, scene_size: [consts::SIZE as f32; 2]}
+    }
+
+    pub fn look_at(eye: Vec<f32>, aim: Vec<f32>) -> Camera{
+        Camera{eye, aim, focal: None, perspective: true, scene_size: [consts::SIZE as f32; 2]}
+    }
+
+    pub fn set_focal(&mut self, focal: f32){
+        self.focal = Some(focal);
+    }
+
+    pub fn set_scene_size(&mut self, width: usize, height: usize){
+        self.scene_size = [width as f32, height as f32];
+    }
+
+    /// the vector from the scene towards the viewer, used for lighting
+    pub fn view_vector(&self) -> Vec<f32>{
+        if !self.perspective{
+            return consts::VIEW.to_vec();
+        }
+        let mut view = vector_subtraction(&self.eye, &self.aim);
+        normalize(&mut view);
+        view
+    }
+
+    pub fn focal_length(&self) -> f32{
+        match self.focal{
+            Some(focal) => focal,
+            None => {
+                let distance = vector_subtraction(&self.eye, &self.aim);
+                distance.iter().map(|d| d * d).sum::<f32>().sqrt()
+            }
+        }
+    }
+
+    /// Returns: the points of matrix in view space, before the perspective
+    /// divide, so they can be clipped against the near and far planes.
+    ///
+    /// z is the depth in front of the camera (larger is closer) and w is
+    /// the distance in front of it, or 1 for an orthographic camera.
+    pub fn view(&self, matrix: &Matrix) -> Matrix{
+        let mut viewed = matrix.clone();
+        if !self.perspective || viewed.matrix_array[0].is_empty(){
+            return viewed;
+        }
+        viewed.multiply_matrixes(&Matrix::make_look_at(&self.eye, &self.aim, &[0.0, 1.0, 0.0]));
+        let camera_z = viewed.matrix_array[2].clone();
+        viewed.multiply_matrixes(&Matrix::make_perspective(self.focal_length()));
+        viewed.matrix_array[2] = camera_z;
+        viewed
+    }
+
+    /// the nearest and furthest w in view space that are drawn, or None for
+    /// an orthographic camera, which draws everything
+    pub fn depth_range(&self) -> Option<(f32, f32)>{
+        if self.perspective{
+            return Some((consts::NEAR_PLANE, consts::FAR_PLANE));
+        }
+        None
+    }
+
+    /// Inputs:   position: a point in view space between the near and far planes
+    ///
+    /// Returns: the point in screen coordinates for a screen of the given
+    /// size, with x and y in pixels, the same z, and 1 / w, which changes
+    /// linearly across the screen where z does not
+    pub fn to_screen(&self, position: [f32; 4], width: usize, height: usize) -> [f32; 4]{
+        let [mut x, mut y, z, w] = position;
+        let mut reciprocal_w = 1.0;
+        if self.perspective{
+            reciprocal_w = 1.0 / w;
+            x = x * reciprocal_w + self.scene_size[0] / 2.0;
+            y = y * reciprocal_w + self.scene_size[1] / 2.0;
+        }
+        // fit the scene in the middle of the screen, keeping its proportions
+        let scale = (width as f32 / self.scene_size[0]).min(height as f32 / self.scene_size[1]);
+        let offset_x = (width as f32 - self.scene_size[0] * scale) / 2.0;
+        let offset_y = (height as f32 - self.scene_size[1] * scale) / 2.0;
+        [x * scale + offset_x, y * scale + offset_y, z, reciprocal_w]
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn assert_close(actual: &[f32], expected: &[f32]){
+        assert!(actual.iter().zip(expected).all(|(a, e)| (a - e).abs() < 1e-4), "{:?} is not {:?}", actual, expected);
+    }
+
+    /// the view space positions of the given points
+    fn view_points(camera: &Camera, points: &[[f32; 3]]) -> Vec<[f32; 4]>{
+        let mut matrix = Matrix::new(0, 0);
+        for point in points{
+            matrix.add_point(point[0], point[1], point[2]);
+        }
+        let viewed = camera.view(&matrix);
+        (0..points.len()).map(|i| [0, 1, 2, 3].map(|row| viewed.matrix_array[row][i])).collect()
+    }
+
+    #[test]
+    fn look_at_down_the_z_axis_only_moves_the_eye_to_the_origin(){
+        let matrix = Matrix::make_look_at(&[0.0, 0.0, 10.0], &[0.0, 0.0, 0.0], &[0.0, 1.0, 0.0]);
+        let mut expected = Matrix::identity();
+        expected.matrix_array[2][3] = -10.0;
+        for row in 0..4{
+            assert_close(&matrix.matrix_array[row], &expected.matrix_array[row]);
+        }
+    }
+
+    #[test]
+    fn look_at_from_the_side_turns_the_aim_onto_the_negative_z_axis(){
+        let matrix = Matrix::make_look_at(&[10.0, 0.0, 0.0], &[0.0, 0.0, 0.0], &[0.0, 1.0, 0.0]);
+        assert_close(&matrix.matrix_array[0], &[0.0, 0.0, -1.0, 0.0]);
+        assert_close(&matrix.matrix_array[1], &[0.0, 1.0, 0.0, 0.0]);
+        assert_close(&matrix.matrix_array[2], &[1.0, 0.0, 0.0, -10.0]);
+    }
+
+    #[test]
+    fn perspective_shrinks_further_points(){
+        let camera = Camera::look_at(vec![0.0, 0.0, 10.0], vec![0.0; 3]);
+        let points = view_points(&camera, &[[1.0, 2.0, 0.0], [1.0, 2.0, -10.0]]);
+        // w is the distance in front of the camera, and the focal length is
+        // the distance to the aim, so shapes at the aim keep their size
+        assert_close(&points[0], &[10.0, 20.0, -10.0, 10.0]);
+        assert_close(&points[1], &[10.0, 20.0, -20.0, 20.0]);
+        let size = consts::SIZE;
+        let middle = size as f32 / 2.0;
+        assert_close(&camera.to_screen(points[0], size, size), &[middle + 1.0, middle + 2.0, -10.0, 0.1]);
+        assert_close(&camera.to_screen(points[1], size, size), &[middle + 0.5, middle + 1.0, -20.0, 0.05]);
+    }
+
+    #[test]
+    fn focal_overrides_the_distance_to_the_aim(){
+        let mut camera = Camera::look_at(vec![0.0, 0.0, 10.0], vec![0.0; 3]);
+        assert_eq!(camera.focal_length(), 10.0);
+        camera.set_focal(5.0);
+        assert_close(&view_points(&camera, &[[1.0, 0.0, 0.0]])[0], &[5.0, 0.0, -10.0, 10.0]);
+    }
+
+    #[test]
+    fn orthographic_camera_fits_the_scene_on_the_screen(){
+        let mut camera = Camera::new();
+        camera.set_scene_size(100, 50);
+        // twice the size, and centered vertically
+        assert_close(&camera.to_screen([10.0, 10.0, 3.0, 1.0], 200, 200), &[20.0, 70.0, 3.0, 1.0]);
+    }
+}
@@ -0,0 +1,167 @@
+use hw_11::{Antialias, Filter, RenderOptions};
+use std::path::PathBuf;
+
+pub const USAGE: &str = "usage: hw-11 [options] [script]
+
+Renders an MDL script, simple_anim.mdl if none is given.
+
+options:
+  -o, --output DIR        directory for saved images, frames and gifs (default .)
+  -b, --basename NAME     name the animation NAME instead of its basename
+  -f, --frames START-END  only render frames START to END, counting from 0,
+                          into a gif named after them like name0010-0020.gif
+  -s, --size WIDTHxHEIGHT fit the scene into a screen of this size
+  -a, --antialias N[:FILTER]
+                          supersample each pixel N by N times, from 1 to 8,
+                          and filter it down with box (default) or tent
+  -j, --threads N         number of threads to render with
+  -n, --dry-run           check the script without rendering anything
+  -h, --help              show this message";
+
+/// What the command line asked for
+pub struct Arguments {
+    pub script: String,
+    pub options: RenderOptions,
+    pub help: bool,
+}
+
+/// Inputs: the command line arguments, without the program name
+/// Returns: the script and options, or a message saying what was wrong
+pub fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Arguments, String> {
+    let mut arguments = Arguments {
+        script: String::from("simple_anim.mdl"),
+        options: RenderOptions::default(),
+        help: false,
+    };
+    let mut script = None;
+    while let Some(arg) = args.next() {
+        // options can also be written as --option=value
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) if arg.starts_with("--") => (flag.to_owned(), Some(value.to_owned())),
+            _ => (arg.clone(), None),
+        };
+        let mut value = |name: &str| {
+            inline_value.clone().or_else(|| args.next()).ok_or_else(|| format!("{} needs a value", name))
+        };
+        match flag.as_str() {
+            "-o" | "--output" => arguments.options.output_dir = PathBuf::from(value(&flag)?),
+            "-b" | "--basename" => arguments.options.basename = Some(value(&flag)?),
+            "-f" | "--frames" => {
+                let frames = value(&flag)?;
+                arguments.options.frame_range = Some(parse_frame_range(&frames).ok_or_else(|| format!("{} is not a frame range like 10-20", frames))?);
+            }
+            "-s" | "--size" => {
+                let size = value(&flag)?;
+                arguments.options.size = Some(parse_size(&size).ok_or_else(|| format!("{} is not a size like 1920x1080", size))?);
+            }
+            "-a" | "--antialias" => {
+                let antialias = value(&flag)?;
+                arguments.options.antialias = Some(parse_antialias(&antialias).ok_or_else(|| format!("{} is not an antialias factor like 4 or 4:tent", antialias))?);
+            }
+            "-j" | "--threads" => {
+                let threads = value(&flag)?;
+                arguments.options.threads = match threads.parse() {
+                    Ok(threads) if threads > 0 => threads,
+                    _ => return Err(format!("{} is not a number of threads", threads)),
+                };
+            }
+            "-n" | "--dry-run" => arguments.options.dry_run = true,
+            "-h" | "--help" => arguments.help = true,
+            _ if flag.starts_with('-') && flag.len() > 1 => return Err(format!("unknown option {}", flag)),
+            _ => {
+                if script.is_some() {
+                    return Err(format!("only one script can be rendered, but {} was also given", arg));
+                }
+                script = Some(arg);
+            }
+        }
+    }
+    if let Some(script) = script {
+        arguments.script = script;
+    }
+    Ok(arguments)
+}
+
+/// Reads a screen size written as WIDTHxHEIGHT, like 1920x1080
+fn parse_size(size: &str) -> Option<(usize, usize)> {
+    let (width, height) = size.split_once('x')?;
+    let width: usize = width.parse().ok()?;
+    let height: usize = height.parse().ok()?;
+    if width == 0 || height == 0 {
+        return None;
+    }
+    Some((width, height))
+}
+
+/// Reads an antialias factor, optionally followed by a filter, like 4:tent
+fn parse_antialias(antialias: &str) -> Option<Antialias> {
+    let (factor, filter) = match antialias.split_once(':') {
+        Some((factor, filter)) => (factor, Filter::from_name(filter)?),
+        None => (antialias, Filter::Box),
+    };
+    let factor: usize = factor.parse().ok()?;
+    if factor == 0 || factor > Antialias::MAX_FACTOR {
+        return None;
+    }
+    Some(Antialias::new(factor, filter))
+}
+
+/// Reads an inclusive range of frames written as START-END, or a single frame
+fn parse_frame_range(frames: &str) -> Option<(usize, usize)> {
+    let (start, end) = match frames.split_once('-') {
+        Some((start, end)) => (start.parse().ok()?, end.parse().ok()?),
+        None => {
+            let frame = frames.parse().ok()?;
+            (frame, frame)
+        }
+    };
+    Some((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Result<Arguments, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn frame_ranges() {
+        assert_eq!(args(&["-f", "10-20"]).unwrap().options.frame_range, Some((10, 20)));
+        assert_eq!(args(&["--frames=7"]).unwrap().options.frame_range, Some((7, 7)));
+        // a backwards range is reported by the renderer, which knows the frame count
+        assert_eq!(args(&["-f", "5-2"]).unwrap().options.frame_range, Some((5, 2)));
+        assert!(args(&["-f", "1-"]).is_err());
+        assert!(args(&["-f", "a-b"]).is_err());
+        assert!(args(&["-f"]).is_err());
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(args(&["-s", "1920x1080"]).unwrap().options.size, Some((1920, 1080)));
+        assert_eq!(args(&["--size=5x7"]).unwrap().options.size, Some((5, 7)));
+        assert!(args(&["-s", "0x100"]).is_err());
+        assert!(args(&["-s", "100"]).is_err());
+        assert!(args(&["-s", "100x-1"]).is_err());
+    }
+
+    #[test]
+    fn antialias_values() {
+        assert_eq!(args(&["-a", "4"]).unwrap().options.antialias, Some(Antialias::new(4, Filter::Box)));
+        assert_eq!(args(&["-a", "2:tent"]).unwrap().options.antialias, Some(Antialias::new(2, Filter::Tent)));
+        assert!(args(&["-a", "0"]).is_err());
+        assert!(args(&["-a", &(Antialias::MAX_FACTOR + 1).to_string()]).is_err());
+        assert!(args(&["-a", "4:blur"]).is_err());
+    }
+
+    #[test]
+    fn script_and_flags() {
+        let arguments = args(&["-n", "face.mdl"]).unwrap();
+        assert_eq!(arguments.script, "face.mdl");
+        assert!(arguments.options.dry_run);
+        assert_eq!(args(&[]).unwrap().script, "simple_anim.mdl");
+        assert!(args(&["a.mdl", "b.mdl"]).is_err());
+        assert!(args(&["--bogus"]).is_err());
+    }
+}
@@ -0,0 +1,273 @@
+//! Cuts polygons and lines down to the part that can be drawn: between the
+//! near and far planes of the camera, and on the screen.
+
+use std::cmp::Ordering;
+
+/// A corner of a polygon being clipped
+#[derive(Clone, Debug)]
+pub struct ClipVertex {
+    /// x, y, z and w in view space before the perspective divide, or x, y,
+    /// z and 1 / w on the screen after it
+    pub position: [f32; 4],
+    /// how much each corner of the original triangle counts at this vertex
+    pub weights: [f32; 3],
+}
+
+impl ClipVertex {
+    /// the vertex t of the way from self to other
+    fn lerp(&self, other: &ClipVertex, t: f32) -> ClipVertex {
+        let mut vertex = self.clone();
+        for v in 0..4 {
+            vertex.position[v] += (other.position[v] - self.position[v]) * t;
+        }
+        for v in 0..3 {
+            vertex.weights[v] += (other.weights[v] - self.weights[v]) * t;
+        }
+        vertex
+    }
+}
+
+/// Returns: where the edge from a to b crosses the plane that distance
+/// measures from. The ends are put in a fixed order first, so that an edge
+/// shared by two triangles is cut at exactly the same point in both and
+/// they still meet without a crack
+fn intersect<F: Fn(&[f32; 4]) -> f32>(a: &ClipVertex, b: &ClipVertex, distance: &F) -> ClipVertex {
+    let in_order = a.position.iter().zip(b.position.iter()).map(|(a, b)| a.total_cmp(b)).find(|order| *order != Ordering::Equal) != Some(Ordering::Greater);
+    let (a, b) = if in_order { (a, b) } else { (b, a) };
+    let (distance_a, distance_b) = (distance(&a.position), distance(&b.position));
+    a.lerp(b, distance_a / (distance_a - distance_b))
+}
+
+/// Sutherland–Hodgman clipping of a convex polygon against one plane.
+/// Returns: the part of the polygon where distance is not negative
+pub fn clip_polygon<F: Fn(&[f32; 4]) -> f32>(polygon: &[ClipVertex], distance: F) -> Vec<ClipVertex> {
+    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+    for (i, current) in polygon.iter().enumerate() {
+        let previous = &polygon[(i + polygon.len() - 1) % polygon.len()];
+        let inside = distance(&current.position) >= 0.0;
+        let previous_inside = distance(&previous.position) >= 0.0;
+        if inside != previous_inside {
+            clipped.push(intersect(previous, current, &distance));
+        }
+        if inside {
+            clipped.push(current.clone());
+        }
+    }
+    clipped
+}
+
+/// Inputs:   polygon: in view space, where w is the distance in front of the camera
+///
+/// Returns: the part of the polygon from near to far in front of the camera
+pub fn clip_to_depth(polygon: &[ClipVertex], near: f32, far: f32) -> Vec<ClipVertex> {
+    let polygon = clip_polygon(polygon, |position| position[3] - near);
+    clip_polygon(&polygon, |position| far - position[3])
+}
+
+/// Inputs:   polygon: in screen space
+///
+/// Returns: the part of the polygon on a screen of the given size
+pub fn clip_to_screen(polygon: &[ClipVertex], width: usize, height: usize) -> Vec<ClipVertex> {
+    let (width, height) = (width as f32, height as f32);
+    let polygon = clip_polygon(polygon, |position| position[0]);
+    let polygon = clip_polygon(&polygon, |position| width - position[0]);
+    let polygon = clip_polygon(&polygon, |position| position[1]);
+    clip_polygon(&polygon, |position| height - position[1])
+}
+
+/// Returns: twice the area of a polygon in screen space, which is positive
+/// when its corners go counterclockwise, so it faces the camera
+pub fn signed_area(polygon: &[ClipVertex]) -> f32 {
+    let mut area = 0.0;
+    for (i, current) in polygon.iter().enumerate() {
+        let next = &polygon[(i + 1) % polygon.len()];
+        area += current.position[0] * next.position[1] - next.position[0] * current.position[1];
+    }
+    area
+}
+
+/// the point t of the way from start to end
+fn lerp(start: &[f32; 4], end: &[f32; 4], t: f32) -> [f32; 4] {
+    let mut point = *start;
+    for v in 0..4 {
+        point[v] += (end[v] - start[v]) * t;
+    }
+    point
+}
+
+/// Inputs:   start, end: the ends of a line in view space
+///
+/// Returns: the part of the line from near to far in front of the camera, if any
+pub fn clip_line_to_depth(mut start: [f32; 4], mut end: [f32; 4], near: f32, far: f32) -> Option<([f32; 4], [f32; 4])> {
+    for plane in [near, -far] {
+        // how far each end is on the side of the plane that is kept
+        let distance = |point: &[f32; 4]| if plane >= 0.0 { point[3] - near } else { far - point[3] };
+        let (distance_start, distance_end) = (distance(&start), distance(&end));
+        if distance_start < 0.0 && distance_end < 0.0 {
+            return None;
+        }
+        if distance_start < 0.0 {
+            start = lerp(&start, &end, distance_start / (distance_start - distance_end));
+        } else if distance_end < 0.0 {
+            end = lerp(&end, &start, distance_end / (distance_end - distance_start));
+        }
+    }
+    Some((start, end))
+}
+
+const LEFT: u8 = 1;
+const RIGHT: u8 = 2;
+const BOTTOM: u8 = 4;
+const TOP: u8 = 8;
+
+/// which sides of the rectangle from min to max a point is past
+fn outcode(point: &[f32; 4], min: [f32; 2], max: [f32; 2]) -> u8 {
+    let mut code = 0;
+    if point[0] < min[0] {
+        code |= LEFT;
+    } else if point[0] > max[0] {
+        code |= RIGHT;
+    }
+    if point[1] < min[1] {
+        code |= BOTTOM;
+    } else if point[1] > max[1] {
+        code |= TOP;
+    }
+    code
+}
+
+/// Cohen–Sutherland clipping of a line in screen space.
+/// Returns: the part of the line inside the rectangle from min to max, if any
+pub fn clip_line(mut start: [f32; 4], mut end: [f32; 4], min: [f32; 2], max: [f32; 2]) -> Option<([f32; 4], [f32; 4])> {
+    let mut start_code = outcode(&start, min, max);
+    let mut end_code = outcode(&end, min, max);
+    loop {
+        if start_code | end_code == 0 {
+            return Some((start, end));
+        }
+        if start_code & end_code != 0 {
+            return None;
+        }
+        // move an end that is outside onto the edge it is past
+        let code = if start_code != 0 { start_code } else { end_code };
+        let t = if code & TOP != 0 {
+            (max[1] - start[1]) / (end[1] - start[1])
+        } else if code & BOTTOM != 0 {
+            (min[1] - start[1]) / (end[1] - start[1])
+        } else if code & RIGHT != 0 {
+            (max[0] - start[0]) / (end[0] - start[0])
+        } else {
+            (min[0] - start[0]) / (end[0] - start[0])
+        };
+        let mut point = lerp(&start, &end, t);
+        // the edge it was moved onto is matched exactly, so rounding can not
+        // leave it just outside and loop forever
+        if code & TOP != 0 {
+            point[1] = max[1];
+        } else if code & BOTTOM != 0 {
+            point[1] = min[1];
+        } else if code & RIGHT != 0 {
+            point[0] = max[0];
+        } else {
+            point[0] = min[0];
+        }
+        if code == start_code {
+            start = point;
+            start_code = outcode(&start, min, max);
+        } else {
+            end = point;
+            end_code = outcode(&end, min, max);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn polygon(positions: &[[f32; 4]]) -> Vec<ClipVertex> {
+        positions.iter().map(|&position| ClipVertex { position, weights: [1.0, 0.0, 0.0] }).collect()
+    }
+
+    fn positions(polygon: &[ClipVertex]) -> Vec<[f32; 4]> {
+        polygon.iter().map(|vertex| vertex.position).collect()
+    }
+
+    #[test]
+    fn polygon_inside_the_depth_range_is_unchanged() {
+        let triangle = polygon(&[[0.0, 0.0, 0.0, 2.0], [1.0, 0.0, 0.0, 3.0], [0.0, 1.0, 0.0, 4.0]]);
+        assert_eq!(positions(&clip_to_depth(&triangle, 1.0, 10.0)), positions(&triangle));
+    }
+
+    #[test]
+    fn polygon_behind_the_near_plane_is_removed() {
+        let triangle = polygon(&[[0.0, 0.0, 0.0, -2.0], [1.0, 0.0, 0.0, 0.5], [0.0, 1.0, 0.0, -1.0]]);
+        assert!(clip_to_depth(&triangle, 1.0, 10.0).is_empty());
+    }
+
+    #[test]
+    fn corner_on_the_near_plane_is_kept() {
+        let triangle = polygon(&[[0.0, 0.0, 0.0, 1.0], [1.0, 0.0, 0.0, 2.0], [0.0, 1.0, 0.0, 2.0]]);
+        assert_eq!(positions(&clip_to_depth(&triangle, 1.0, 10.0)), positions(&triangle));
+    }
+
+    #[test]
+    fn cutting_off_one_corner_leaves_a_quad() {
+        let triangle = polygon(&[[0.0, 0.0, 0.0, 0.0], [4.0, 0.0, 0.0, 4.0], [0.0, 4.0, 0.0, 4.0]]);
+        let clipped = clip_to_depth(&triangle, 1.0, 10.0);
+        assert_eq!(clipped.len(), 4);
+        assert!(clipped.iter().all(|vertex| vertex.position[3] >= 1.0));
+        // the weights are cut along with the positions
+        assert!(clipped.iter().all(|vertex| vertex.weights == [1.0, 0.0, 0.0]));
+    }
+
+    #[test]
+    fn shared_edge_is_cut_at_the_same_point_both_ways() {
+        let a = ClipVertex { position: [0.3, 0.7, 0.1, 0.2], weights: [1.0, 0.0, 0.0] };
+        let b = ClipVertex { position: [5.1, -2.9, 3.3, 7.7], weights: [0.0, 1.0, 0.0] };
+        let distance = |position: &[f32; 4]| position[3] - 1.0;
+        assert_eq!(intersect(&a, &b, &distance).position, intersect(&b, &a, &distance).position);
+    }
+
+    #[test]
+    fn polygon_larger_than_the_screen_is_cut_to_it() {
+        let triangle = polygon(&[[-100.0, -100.0, 0.0, 1.0], [300.0, -100.0, 0.0, 1.0], [-100.0, 300.0, 0.0, 1.0]]);
+        let clipped = clip_to_screen(&triangle, 100, 50);
+        assert_eq!(signed_area(&clipped), 2.0 * 100.0 * 50.0);
+        assert!(clipped.iter().all(|vertex| (0.0..=100.0).contains(&vertex.position[0]) && (0.0..=50.0).contains(&vertex.position[1])));
+    }
+
+    #[test]
+    fn line_depth_clipping() {
+        assert_eq!(clip_line_to_depth([0.0, 0.0, 0.0, -1.0], [0.0, 0.0, 0.0, 0.5], 1.0, 10.0), None);
+        assert_eq!(clip_line_to_depth([0.0, 0.0, 0.0, 11.0], [0.0, 0.0, 0.0, 12.0], 1.0, 10.0), None);
+        assert_eq!(
+            clip_line_to_depth([0.0, 0.0, 0.0, 0.0], [4.0, 0.0, 0.0, 20.0], 1.0, 10.0),
+            Some(([0.2, 0.0, 0.0, 1.0], [2.0, 0.0, 0.0, 10.0]))
+        );
+    }
+
+    #[test]
+    fn line_screen_clipping() {
+        let (min, max) = ([0.0, 0.0], [10.0, 10.0]);
+        let inside = ([1.0, 2.0, 0.0, 1.0], [3.0, 4.0, 0.0, 1.0]);
+        assert_eq!(clip_line(inside.0, inside.1, min, max), Some(inside));
+        // both ends past the same side
+        assert_eq!(clip_line([-1.0, 2.0, 0.0, 1.0], [-5.0, 8.0, 0.0, 1.0], min, max), None);
+        // on the edge itself
+        let edge = ([0.0, 0.0, 0.0, 1.0], [0.0, 10.0, 0.0, 1.0]);
+        assert_eq!(clip_line(edge.0, edge.1, min, max), Some(edge));
+        // a vertical line through the screen
+        assert_eq!(
+            clip_line([5.0, -5.0, 0.0, 1.0], [5.0, 15.0, 0.0, 1.0], min, max),
+            Some(([5.0, 0.0, 0.0, 1.0], [5.0, 10.0, 0.0, 1.0]))
+        );
+        // a line past two corners that misses the screen
+        assert_eq!(clip_line([-5.0, 8.0, 0.0, 1.0], [8.0, 21.0, 0.0, 1.0], min, max), None);
+        // a diagonal through both corners
+        assert_eq!(
+            clip_line([-5.0, -5.0, 0.0, 1.0], [15.0, 15.0, 0.0, 1.0], min, max),
+            Some(([0.0, 0.0, 0.0, 1.0], [10.0, 10.0, 0.0, 1.0]))
+        );
+    }
+}
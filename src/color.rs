@@ -1,21 +1,32 @@
 use std::fmt;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct Color{
     pub r: u8,
     pub g: u8,
     pub b: u8,
 }
 
+impl Default for Color {
+    fn default() -> Color {
+        Color::new()
+    }
+}
+
 impl Color{
     pub fn new() -> Color{
         Color{r: 0, g:0, b:0}
     }
 
     pub const fn new_color(r: u8, g: u8, b: u8) -> Color{
-        Color{r: r, g: g, b: b}
+        Color{r, g, b}
     }
     
+    /// makes a color from floating point channels, clamping each one to 0-255
+    pub fn from_f32(r: f32, g: f32, b: f32) -> Color{
+        Color{r: r.clamp(0.0, 255.0) as u8, g: g.clamp(0.0, 255.0) as u8, b: b.clamp(0.0, 255.0) as u8}
+    }
+
     pub fn plot_color(&mut self, new_color: &Color){
         self.r = new_color.r;
         self.g = new_color.g;
@@ -1,27 +1,69 @@
-use crate::ReflectionValue;
+use crate::camera::Camera;
+use crate::clip::{clip_line, clip_line_to_depth, clip_to_depth, clip_to_screen, signed_area, ClipVertex};
+use crate::lighting::{Constants, Lighting};
 use crate::Color;
-use crate::CurveType;
+use crate::consts;
+use crate::matrix::CurveType;
 use crate::Image;
 use crate::Matrix;
 use crate::gmath::*;
 use std::f32;
+use std::mem;
+
+/// How `draw_polygons` fills in each triangle
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ShadingType{
+    Flat,
+    Gouraud,
+    Phong,
+    Wireframe
+}
+
+/// how many bits of a pixel vertices are snapped to when filling triangles
+const SUBPIXEL_BITS: u32 = 8;
+/// triangles reaching further than this many pixels from the screen are left
+/// out, since their edge functions could overflow
+const MAX_COORDINATE: f32 = (1 << 21) as f32;
+
+/// Inputs:   a, b: the ends of an edge, in fixed point
+///
+/// p: the point to test, in fixed point
+///
+/// Returns: twice the signed area of the triangle a b p, which is positive
+/// when p is to the left of the edge from a to b and 0 when it is on it
+fn edge_function(a: (i64, i64), b: (i64, i64), p: (i64, i64)) -> i64 {
+    (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0)
+}
+
+/// Whether the edge from a to b of a counterclockwise triangle is a top or a
+/// left edge. y goes up, so a top edge runs in the -x direction and a left
+/// edge runs down
+fn is_top_left(a: (i64, i64), b: (i64, i64)) -> bool {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    dy < 0 || (dy == 0 && dx < 0)
+}
+
+/// blends the attributes of the three vertices of a triangle with the given weights
+fn blend(attributes: &[Vec<f32>; 3], weights: [f32; 3]) -> Vec<f32> {
+    (0..attributes[0].len()).map(|v| attributes[0][v] * weights[0] + attributes[1][v] * weights[1] + attributes[2][v] * weights[2]).collect()
+}
+
+/// the x, y, z and w of point i of a matrix
+fn view_position(matrix: &Matrix, i: usize) -> [f32; 4] {
+    [matrix.matrix_array[0][i], matrix.matrix_array[1][i], matrix.matrix_array[2][i], matrix.matrix_array[3][i]]
+}
 
 impl Image {
+    #[allow(clippy::too_many_arguments)]
     pub fn draw_line(&mut self, mut x0: i32, mut y0: i32, mut z0: f32, mut x1: i32, mut y1: i32, mut z1: f32, color: &Color) {
-        // println!("x0: {}, y0: {}, x1: {}, y1: {}", x0, y0, x1, y1);
-        if (x0 >= 500 &&  x1 >= 500) || (y0 >= 500 && y1 >= 500) || (x0 < 0 &&  x1 < 0) || (y0 < 0 && y1 < 0){
+        let (width, height) = (self.width as i32, self.height as i32);
+        if (x0 >= width && x1 >= width) || (y0 >= height && y1 >= height) || (x0 < 0 && x1 < 0) || (y0 < 0 && y1 < 0){
             return;
         }
         if x0 > x1 {
-            let mut tmp = x0;
-            x0 = x1;
-            x1 = tmp;
-            tmp = y0;
-            y0 = y1;
-            y1 = tmp;
-            let tmp = z0;
-            z0 = z1;
-            z1 = tmp;
+            mem::swap(&mut x0, &mut x1);
+            mem::swap(&mut y0, &mut y1);
+            mem::swap(&mut z0, &mut z1);
         }
         let slope: f32 = (y1 - y0) as f32 / (x1 - x0) as f32;
         if slope > 1.0 {
@@ -32,7 +74,7 @@ impl Image {
             let z_rate = (z1 - z0) / (y1 - y0) as f32;
             let a = 2 * (y1 - y0);
             let b = -2 * (x1 - x0);
-            let mut d = 1 / 2 * a + b; // emphasis on controlling y
+            let mut d = b; // emphasis on controlling y
             while y <= y1 {
                 if self.plot(x, y, z, color){
                     if d < 0 {
@@ -55,7 +97,7 @@ impl Image {
             let z_rate = (z1 - z0) / (x1 - x0) as f32;
             let a = 2 * (y1 - y0);
             let b = -2 * (x1 - x0);
-            let mut d = a + 1 / 2 * b; // emphasis on controlling x
+            let mut d = a; // emphasis on controlling x
             while x <= x1 {
                 if self.plot(x, y, z, color){
                     if d > 0 {
@@ -78,7 +120,7 @@ impl Image {
             let z_rate = (z1 - z0) / (y1 - y0) as f32;
             let a = 2 * (y1 - y0); // since this is negative, you dont need to make the next part negative
             let b = 2 * (x1 - x0);
-            let mut d = 1 / 2 * a + b; // emphasis on controlling x
+            let mut d = b; // emphasis on controlling x
             while y >= y1 {
                 if self.plot(x, y, z, color){
                     if d < 0 {
@@ -101,7 +143,7 @@ impl Image {
             let z_rate = (z1 - z0) / (x1 - x0) as f32;
             let a = 2 * (y1 - y0); // since this is negative, you dont need to make the next part negative
             let b = 2 * (x1 - x0);
-            let mut d = a + 1 / 2 * b; // emphasis on controlling y
+            let mut d = a; // emphasis on controlling y
             while x <= x1 {
                 if self.plot(x, y, z, color){
                     if d > 0 {
@@ -119,17 +161,36 @@ impl Image {
         }
     }
 
-    pub fn draw_lines(&mut self, matrix: &Matrix, color: &Color) {
-        for i in (0..matrix.matrix_array[0].len()).step_by(2) {
-            self.draw_line(
-                matrix.matrix_array[0][i] as i32,
-                matrix.matrix_array[1][i] as i32,
-                matrix.matrix_array[2][i] as f32,
-                matrix.matrix_array[0][i + 1] as i32,
-                matrix.matrix_array[1][i + 1] as i32,
-                matrix.matrix_array[2][i + 1] as f32,
-                color,
-            );
+    pub fn draw_lines(&mut self, matrix: &Matrix, color: &Color, camera: &Camera) {
+        let viewed = camera.view(matrix);
+        for i in (0..viewed.matrix_array[0].len()).step_by(2) {
+            self.draw_clipped_line(camera, view_position(&viewed, i), view_position(&viewed, i + 1), color);
+        }
+    }
+
+    /*======== void draw_clipped_line() ==========
+    Inputs: start, end: the ends of the line in view space
+    Returns:
+
+    Draws the part of the line between the near and far planes, clipped to
+    the screen with Cohen–Sutherland so that draw_line only gets points on it.
+    ====================*/
+    fn draw_clipped_line(&mut self, camera: &Camera, start: [f32; 4], end: [f32; 4], color: &Color) {
+        let (start, end) = match camera.depth_range() {
+            Some((near, far)) => match clip_line_to_depth(start, end, near, far) {
+                Some(line) => line,
+                None => return,
+            },
+            None => (start, end),
+        };
+        // z is clipped divided by w, as in clip_triangle
+        let [start, end] = [start, end].map(|point| {
+            let [x, y, z, reciprocal_w] = camera.to_screen(point, self.width, self.height);
+            [x, y, z * reciprocal_w, reciprocal_w]
+        });
+        let max = [(self.width - 1) as f32, (self.height - 1) as f32];
+        if let Some((start, end)) = clip_line(start, end, [0.0, 0.0], max) {
+            self.draw_line(start[0] as i32, start[1] as i32, start[2] / start[3], end[0] as i32, end[1] as i32, end[2] / end[3], color);
         }
     }
 
@@ -141,103 +202,190 @@ impl Image {
     ///
     ///color c
     ///
+    ///camera
+    ///
+    ///shading type
+    ///
     ///Returns:
     ///
-    ///Goes through polygons 3 points at a time, drawing
-    ///lines connecting each points to create bounding triangles
+    ///Goes through polygons 3 points at a time, filling in
+    ///each front facing triangle using the given shading type.
+    ///Lighting uses the points as given, while the triangles are
+    ///drawn where the camera projects them, clipped to the near
+    ///and far planes and to the screen.
+    ///Wireframe draws the edges of each triangle in color c.
     ///====================
-    pub fn draw_polygons(&mut self, polygons: &Matrix, c: &Color, view: &mut Vec<f32>, ambient_color: &Color, point_light_vector: &mut Vec<f32>, point_light_color: &Color, ambient_reflect: &ReflectionValue, direct_reflect: &ReflectionValue, specular_reflect: &ReflectionValue) {
+    pub fn draw_polygons(&mut self, polygons: &Matrix, c: &Color, camera: &Camera, lighting: &Lighting, constants: &Constants, shading: &ShadingType) {
+        let lights = lighting.active_lights();
+        let welded = match shading {
+            ShadingType::Gouraud | ShadingType::Phong => Some(polygons.weld_vertices(consts::WELD_EPSILON)),
+            _ => None,
+        };
+        let view = &mut camera.view_vector();
+        let viewed = camera.view(polygons);
+        let corners = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
         for i in (0..polygons.matrix_array[0].len()).step_by(3) {
+            let triangle: Vec<ClipVertex> = (0..3).map(|v| ClipVertex { position: view_position(&viewed, i + v), weights: corners[v] }).collect();
+            let polygon = self.clip_triangle(camera, &triangle);
+            if polygon.len() < 3 || signed_area(&polygon) <= 0.0 {
+                continue;
+            }
             let normal = &mut polygons.calculate_normal(i);
-            if normal[2] > 0.0 {
-                // self.draw_line(
-                //     polygons.matrix_array[0][i] as i32,
-                //     polygons.matrix_array[1][i] as i32,
-                //     polygons.matrix_array[2][i] as f32,
-                //     polygons.matrix_array[0][i + 1] as i32,
-                //     polygons.matrix_array[1][i + 1] as i32,
-                //     polygons.matrix_array[2][i + 1] as f32,
-                //     c,
-                // );
-                // self.draw_line(
-                //     polygons.matrix_array[0][i + 1] as i32,
-                //     polygons.matrix_array[1][i + 1] as i32,
-                //     polygons.matrix_array[2][i + 1] as f32,
-                //     polygons.matrix_array[0][i + 2] as i32,
-                //     polygons.matrix_array[1][i + 2] as i32,
-                //     polygons.matrix_array[2][i + 2] as f32,
-                //     c,
-                // );
-                // self.draw_line(
-                //     polygons.matrix_array[0][i + 2] as i32,
-                //     polygons.matrix_array[1][i + 2] as i32,
-                //     polygons.matrix_array[2][i + 2] as f32,
-                //     polygons.matrix_array[0][i] as i32,
-                //     polygons.matrix_array[1][i] as i32,
-                //     polygons.matrix_array[2][i] as f32,
-                //     c,
-                // );
-                let color = &get_lighting(normal, view, ambient_color, point_light_color, point_light_vector, ambient_reflect, direct_reflect, specular_reflect);
-                self.scanline_convert(
-                    polygons.matrix_array[0][i],
-                    polygons.matrix_array[1][i],
-                    polygons.matrix_array[2][i],
-                    polygons.matrix_array[0][i + 1],
-                    polygons.matrix_array[1][i + 1],
-                    polygons.matrix_array[2][i + 1],
-                    polygons.matrix_array[0][i + 2],
-                    polygons.matrix_array[1][i + 2],
-                    polygons.matrix_array[2][i + 2],
-                    color
-                )
+            match shading {
+                ShadingType::Flat => {
+                    let color = get_lighting(normal, view, &lighting.ambient, &lights, constants, lighting.specular_model);
+                    self.fill_polygon(&polygon, |_| color);
+                }
+                ShadingType::Gouraud => {
+                    // light each vertex, then blend the colors across the triangle
+                    let welded = welded.as_ref().unwrap();
+                    let colors = [i, i + 1, i + 2].map(|v| {
+                        let color = get_lighting(&mut welded.point_normal(v).clone(), view, &lighting.ambient, &lights, constants, lighting.specular_model);
+                        vec![color.r as f32, color.g as f32, color.b as f32]
+                    });
+                    self.fill_polygon(&polygon, |weights| {
+                        let color = blend(&colors, weights);
+                        Color::from_f32(color[0], color[1], color[2])
+                    });
+                }
+                ShadingType::Phong => {
+                    // blend the vertex normals across the triangle, then light each pixel
+                    let welded = welded.as_ref().unwrap();
+                    let normals = [i, i + 1, i + 2].map(|v| welded.point_normal(v).clone());
+                    self.fill_polygon(&polygon, |weights| {
+                        get_lighting(&mut blend(&normals, weights), view, &lighting.ambient, &lights, constants, lighting.specular_model)
+                    });
+                }
+                ShadingType::Wireframe => {
+                    for (start, end) in [(0, 1), (1, 2), (2, 0)] {
+                        self.draw_clipped_line(camera, triangle[start].position, triangle[end].position, c);
+                    }
+                }
             }
         }
     }
 
-    /*======== void scanline_convert() ==========
-    Inputs: x0 y0 z0 x1 y1 z1 x2 y2 z2: i32
-            self screen
+    /*======== Vec<ClipVertex> clip_triangle() ==========
+    Inputs: triangle: the corners of a triangle in view space
+    Returns: the polygon left of the triangle on the screen, as x, y, z and
+             1 / w, after
+             Sutherland–Hodgman clipping against the near and far planes,
+             then against the edges of the screen. It is empty when none
+             of the triangle can be seen
+    ====================*/
+    fn clip_triangle(&self, camera: &Camera, triangle: &[ClipVertex]) -> Vec<ClipVertex> {
+        let mut polygon = match camera.depth_range() {
+            Some((near, far)) => clip_to_depth(triangle, near, far),
+            None => triangle.to_vec(),
+        };
+        // z and the weights only change linearly across the screen once
+        // they are divided by w, so they are clipped that way
+        for vertex in polygon.iter_mut() {
+            let position = camera.to_screen(vertex.position, self.width, self.height);
+            vertex.position = [position[0], position[1], position[2] * position[3], position[3]];
+            vertex.weights = vertex.weights.map(|weight| weight * position[3]);
+        }
+        let mut polygon = clip_to_screen(&polygon, self.width, self.height);
+        for vertex in polygon.iter_mut() {
+            vertex.position[2] /= vertex.position[3];
+            vertex.weights = vertex.weights.map(|weight| weight / vertex.position[3]);
+        }
+        polygon
+    }
+
+    /*======== void fill_polygon() ==========
+    Inputs: polygon: a clipped triangle in screen coordinates
+            shade: turns the barycentric weights of a pixel in the original
+                   triangle into its color
     Returns:
 
-    Fills in polygon i by drawing consecutive horizontal (or vertical) lines.
+    Splits the convex polygon into a fan of triangles around its first
+    vertex and rasterizes each one.
+    ====================*/
+    fn fill_polygon<F: FnMut([f32; 3]) -> Color>(&mut self, polygon: &[ClipVertex], mut shade: F) {
+        for k in 1..polygon.len() - 1 {
+            let fan = [&polygon[0], &polygon[k], &polygon[k + 1]];
+            let points = fan.map(|vertex| vertex.position);
+            self.rasterize_triangle(points, |weights| {
+                let mut original = [0.0; 3];
+                for (vertex, weight) in fan.iter().zip(weights) {
+                    for (sum, vertex_weight) in original.iter_mut().zip(vertex.weights) {
+                        *sum += vertex_weight * weight;
+                    }
+                }
+                shade(original)
+            });
+        }
+    }
 
-    Color should be set differently for each polygon.
+    /*======== void rasterize_triangle() ==========
+    Inputs: points: the x, y, z and 1 / w of each vertex on the screen
+            shade: turns the barycentric weights of a pixel, one for each
+                   vertex, into its color
+    Returns:
+
+    Fills in every pixel whose center is inside the triangle, using the edge
+    function of each side. Vertices are snapped to 1/256 of a pixel so that
+    the edge functions are exact, and a center that lies exactly on an edge
+    is only filled for top and left edges. Triangles that share an edge
+    therefore fill each pixel along it exactly once. The weights are
+    corrected for perspective with 1 / w, which does change linearly across
+    the screen, before z and the color are blended with them.
     ====================*/
-    fn scanline_convert(&mut self, x0: f32, y0: f32, z0: f32, x1: f32, y1: f32, z1: f32, x2: f32, y2: f32, z2: f32, color: &Color) {
-        let mut polygons = [(x0, y0, z0), (x1, y1, z1), (x2, y2, z2)];
-        polygons.sort_by_key(|k| (k.1 as i32, k.0 as i32, k.2 as i32));
-        // println!("{:?}", polygons);
-        let mut past_midpoint = false;
-        let mut x0 = polygons[0].0;
-        let mut x1 = polygons[0].0;
-        let mut z0 = polygons[0].2;
-        let mut z1 = polygons[0].2;
-        let d0 = (polygons[2].1 as i32 - polygons[0].1 as i32) + 1;
-        let d1 = (polygons[1].1 as i32 - polygons[0].1 as i32) + 1;
-        let d2 = (polygons[2].1 as i32 - polygons[1].1 as i32) + 1;
-        let dx0 = if d0 > 0 {(polygons[2].0 - polygons[0].0) / d0 as f32} else {0.0};
-        let dz0 = if d0 > 0 {(polygons[2].2 - polygons[0].2) / d0 as f32} else {0.0};
-        let mut dx1 = if d1 > 0 {(polygons[1].0 - polygons[0].0) / d1 as f32} else {0.0};
-        let mut dz1 = if d1 > 0 {(polygons[1].2 - polygons[0].2) / d1 as f32} else {0.0};
-        let dx1_1 = if d2 > 0 {(polygons[2].0 - polygons[1].0) / d2 as f32} else {0.0};
-        let dz1_1 = if d2 > 0 {(polygons[2].2 - polygons[1].2) / d2 as f32} else {0.0};
-        if (polygons[2].1 - polygons[1].1) as i32 == 0{
-            past_midpoint = true
+    fn rasterize_triangle<F: FnMut([f32; 3]) -> Color>(&mut self, points: [[f32; 4]; 3], mut shade: F) {
+        // also leaves out triangles with NaN coordinates
+        if !points.iter().all(|point| point[0].abs() < MAX_COORDINATE && point[1].abs() < MAX_COORDINATE) {
+            return;
+        }
+        let one = 1i64 << SUBPIXEL_BITS;
+        let fixed = points.map(|point| ((point[0] * one as f32).round() as i64, (point[1] * one as f32).round() as i64));
+        // the vertices are put in counterclockwise order
+        let mut area = edge_function(fixed[0], fixed[1], fixed[2]);
+        let order = if area < 0 { [0, 2, 1] } else { [0, 1, 2] };
+        area = area.abs();
+        if area == 0 {
+            return;
+        }
+        let vertices = order.map(|v| fixed[v]);
+        // edge v is across from vertex v, so its edge function is the weight of vertex v
+        let edges = [(vertices[1], vertices[2]), (vertices[2], vertices[0]), (vertices[0], vertices[1])];
+        let bias = edges.map(|(a, b)| if is_top_left(a, b) { 0 } else { -1 });
+
+        // pixel x covers x to x + 1, with its center at x + 1/2
+        let first_pixel = |low: i64| ((low - one / 2) as f64 / one as f64).ceil() as i64;
+        let last_pixel = |high: i64| ((high - one / 2) as f64 / one as f64).floor() as i64;
+        let first_x = first_pixel(vertices.iter().map(|vertex| vertex.0).min().unwrap()).max(0);
+        let last_x = last_pixel(vertices.iter().map(|vertex| vertex.0).max().unwrap()).min(self.width as i64 - 1);
+        let first_y = first_pixel(vertices.iter().map(|vertex| vertex.1).min().unwrap()).max(0);
+        let last_y = last_pixel(vertices.iter().map(|vertex| vertex.1).max().unwrap()).min(self.height as i64 - 1);
+        if first_x > last_x || first_y > last_y {
+            return;
         }
-        // for y in y0 as i32..=y0 as i32+1 {
-        for y in polygons[0].1 as i32..=polygons[2].1 as i32{
-            if y >= polygons[1].1 as i32 && !past_midpoint{
-                dx1 = dx1_1;
-                dz1 = dz1_1;
-                x1 = polygons[1].0;
-                z1 = polygons[1].2;
-                past_midpoint = true;
+        let center = |pixel: i64| pixel * one + one / 2;
+        let step_x = edges.map(|(a, b)| -(b.1 - a.1) * one);
+        let step_y = edges.map(|(a, b)| (b.0 - a.0) * one);
+        let mut row_start = edges.map(|(a, b)| edge_function(a, b, (center(first_x), center(first_y))));
+        for y in first_y..=last_y {
+            let mut distances = row_start;
+            for x in first_x..=last_x {
+                if (0..3).all(|v| distances[v] + bias[v] >= 0) {
+                    let mut weights = [0.0; 3];
+                    for v in 0..3 {
+                        weights[order[v]] = (distances[v] as f64 / area as f64) as f32 * points[order[v]][3];
+                    }
+                    let total = weights[0] + weights[1] + weights[2];
+                    let weights = weights.map(|weight| weight / total);
+                    let z = weights[0] * points[0][2] + weights[1] * points[1][2] + weights[2] * points[2][2];
+                    let color = shade(weights);
+                    self.plot(x as i32, y as i32, z, &color);
+                }
+                for v in 0..3 {
+                    distances[v] += step_x[v];
+                }
+            }
+            for v in 0..3 {
+                row_start[v] += step_y[v];
             }
-            self.draw_line(x0 as i32, y, z0, x1 as i32, y, z1, color);
-            x0 += dx0;
-            x1 += dx1;
-            z0 += dz0;
-            z1 += dz1;
         }
     }
 }
@@ -282,6 +430,7 @@ impl Matrix {
     }
 
     /// x2, y2, x3, y3 are rx0, ry0, rx1, ry1 respectively if hermier
+    #[allow(clippy::too_many_arguments)]
     pub fn add_curve(
         &mut self,
         x0: f32,
@@ -317,18 +466,18 @@ impl Matrix {
     /// add_box()
     /// Inputs:   matrix * edges
     ///
-    ///             double x
+    /// double x
     ///
-    ///             double y
+    /// double y
     ///
-    ///             double z
+    /// double z
     ///
-    ///             double width
+    /// double width
     ///
-    ///             double height
+    /// double height
+    ///
+    /// double depth
     ///
-    ///             double depth
-
     /// add the points for a rectagular prism whose
     /// upper-left-front corner is (x, y, z) with width,
     /// height and depth dimensions.
@@ -420,13 +569,13 @@ impl Matrix {
     /// double cz
     /// double r
     /// int step  
-
+    ///
     /// adds all the points for a sphere with center (cx, cy, cz)
     /// and radius r using step points per circle/semicircle.
-
+    ///
     /// Since edges are drawn using 2 points, add each point twice,
     /// or add each point and then another point 1 pixel away.
-
+    ///
     /// should call generate_sphere to create the necessary points
     pub fn add_sphere(&mut self, cx: f32, cy: f32, cz: f32, r: f32, step: i32) {
         let lat_start: usize = 0;
@@ -507,7 +656,7 @@ impl Matrix {
                 matrix.add_point(x, y, z);
             }
         }
-        return matrix;
+        matrix
     }
 
     /// add_torus()
@@ -522,7 +671,7 @@ impl Matrix {
     ///
     /// adds all the points required for a torus with center (cx, cy, cz),
     /// circle radius r1 and torus radius r2 using step points per circle.
-
+    ///
     /// should call generate_torus to create the necessary points
     pub fn add_torus(&mut self, cx: f32, cy: f32, cz: f32, r1: f32, r2: f32, step: i32) {
         let points_matrix = Matrix::generate_torus(cx, cy, cz, r1, r2, step);
@@ -606,30 +755,30 @@ impl Matrix {
                 matrix.add_point(x, y, z);
             }
         }
-        return matrix;
+        matrix
     }
 
     ///======== void add_polygon() ==========
     ///
     ///Inputs:   struct matrix *polygons
     ///
-    ///            x0: f32
+    /// x0: f32
     ///
-    ///            y0: f32
+    /// y0: f32
     ///
-    ///            z0: f32
+    /// z0: f32
     ///
-    ///            x1: f32
+    /// x1: f32
     ///
-    ///            y1: f32
+    /// y1: f32
     ///
-    ///            z1: f32
+    /// z1: f32
     ///
-    ///            x2: f32
+    /// x2: f32
     ///
-    ///            y2: f32
+    /// y2: f32
     ///
-    ///            z2: f32
+    /// z2: f32
     ///
     ///Returns:
     ///
@@ -637,6 +786,7 @@ impl Matrix {
     ///and (x2, y2, z2) to the polygon matrix. They
     ///define a single triangle surface.
     ///====================
+    #[allow(clippy::too_many_arguments)]
     pub fn add_polygon(
         &mut self,
         x0: f32,
@@ -650,9 +800,12 @@ impl Matrix {
         z2: f32,
     ) {
         // check for degen triangles, if it is, then don't add
-        if (x0 as i32, y0 as i32, z0 as i32) != (x1 as i32, y1 as i32, z1 as i32)
-            && (x0 as i32, y0 as i32, z0 as i32) != (x2 as i32, y2 as i32, z2 as i32)
-            && (x1 as i32, y1 as i32, z1 as i32) != (x2 as i32, y2 as i32, z2 as i32)
+        // points are compared exactly, not by whole pixel: the rasterizer
+        // works in fractions of a pixel, and meshes are often modelled much
+        // smaller than the screen and scaled up later
+        if (x0, y0, z0) != (x1, y1, z1)
+            && (x0, y0, z0) != (x2, y2, z2)
+            && (x1, y1, z1) != (x2, y2, z2)
         {
             self.add_point(x0, y0, z0);
             self.add_point(x1, y1, z1);
@@ -660,3 +813,116 @@ impl Matrix {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangle(positions: [[f32; 4]; 3]) -> Vec<ClipVertex> {
+        let weights = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+        positions.iter().zip(weights).map(|(&position, weights)| ClipVertex { position, weights }).collect()
+    }
+
+    #[test]
+    fn clip_triangle_on_the_screen_is_unchanged() {
+        let image = Image::new(consts::SIZE, consts::SIZE);
+        let corners = triangle([[10.0, 10.0, 5.0, 1.0], [100.0, 10.0, 6.0, 1.0], [10.0, 100.0, 7.0, 1.0]]);
+        let clipped = image.clip_triangle(&Camera::new(), &corners);
+        assert_eq!(clipped.len(), 3);
+        for (vertex, corner) in clipped.iter().zip(corners.iter()) {
+            assert_eq!(vertex.position, corner.position);
+            assert_eq!(vertex.weights, corner.weights);
+        }
+    }
+
+    #[test]
+    fn clip_triangle_keeps_the_part_on_the_screen() {
+        let image = Image::new(100, 100);
+        let mut camera = Camera::new();
+        camera.set_scene_size(100, 100);
+        let corners = triangle([[-50.0, 50.0, 0.0, 1.0], [150.0, 50.0, 0.0, 1.0], [50.0, 150.0, 0.0, 1.0]]);
+        let clipped = image.clip_triangle(&camera, &corners);
+        assert!(!clipped.is_empty());
+        for vertex in clipped.iter() {
+            assert!((0.0..=100.0).contains(&vertex.position[0]) && (0.0..=100.0).contains(&vertex.position[1]));
+            assert!((vertex.weights.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn clip_triangle_behind_the_camera_is_empty() {
+        let image = Image::new(consts::SIZE, consts::SIZE);
+        let camera = Camera::look_at(vec![0.0, 0.0, 10.0], vec![0.0; 3]);
+        let corners = triangle([[0.0, 0.0, 1.0, -1.0], [10.0, 0.0, 1.0, -1.0], [0.0, 10.0, 1.0, -2.0]]);
+        assert!(image.clip_triangle(&camera, &corners).is_empty());
+    }
+
+    #[test]
+    fn triangles_smaller_than_a_pixel_are_kept() {
+        let mut polygons = Matrix::new(0, 0);
+        polygons.add_polygon(0.1, 0.1, 0.0, 0.6, 0.1, 0.0, 0.1, 0.6, 0.0);
+        assert_eq!(polygons.matrix_array[0].len(), 3);
+        // scaled up, it covers whole pixels
+        polygons.multiply_matrixes(&Matrix::make_scale(100.0, 100.0, 1.0));
+        let mut image = Image::new(100, 100);
+        image.draw_polygons(&polygons, &Color::new_color(255, 255, 255), &Camera::new(), &Lighting::new(), &Constants::default(), &ShadingType::Wireframe);
+        assert!(image.screen.iter().flatten().any(|color| *color != Color::new()));
+    }
+
+    /// how many of the triangles fill each pixel of a width by height screen,
+    /// indexed by y and then x
+    fn coverage(width: usize, height: usize, triangles: &[[[f32; 2]; 3]]) -> Vec<Vec<u32>> {
+        let mut counts = vec![vec![0; width]; height];
+        for corners in triangles {
+            let mut image = Image::new(width, height);
+            image.rasterize_triangle(corners.map(|[x, y]| [x, y, 0.0, 1.0]), |_| Color::new_color(255, 255, 255));
+            for (y, row) in counts.iter_mut().enumerate() {
+                for (x, count) in row.iter_mut().enumerate() {
+                    if image.screen[height - 1 - y][x] != Color::new() {
+                        *count += 1;
+                    }
+                }
+            }
+        }
+        counts
+    }
+
+    #[test]
+    fn quad_split_along_a_diagonal_fills_each_pixel_once() {
+        let quad = [[0.0, 0.0], [4.0, 0.0], [4.0, 4.0], [0.0, 4.0]];
+        // the diagonal runs through the centers of the pixels along it
+        for triangles in [[[quad[0], quad[1], quad[2]], [quad[0], quad[2], quad[3]]], [[quad[0], quad[1], quad[3]], [quad[1], quad[2], quad[3]]]] {
+            assert_eq!(coverage(4, 4, &triangles), vec![vec![1; 4]; 4]);
+        }
+    }
+
+    #[test]
+    fn triangles_sharing_edges_fill_each_pixel_once() {
+        // a fan around a point, with edges at awkward angles and through pixel centers
+        let center = [5.5, 4.5];
+        let rim = [[0.3, 0.5], [10.5, 0.1], [11.0, 9.5], [5.5, 10.0], [0.0, 8.5]];
+        let triangles: Vec<[[f32; 2]; 3]> = (0..rim.len()).map(|i| [center, rim[i], rim[(i + 1) % rim.len()]]).collect();
+        let counts = coverage(12, 12, &triangles);
+        assert!(counts.iter().flatten().all(|&count| count <= 1), "{:?}", counts);
+        // the same polygon split another way fills the same pixels, so there are no gaps either
+        let other_split: Vec<[[f32; 2]; 3]> = (1..rim.len() - 1).map(|i| [rim[0], rim[i], rim[i + 1]]).collect();
+        assert_eq!(counts, coverage(12, 12, &other_split));
+    }
+
+    #[test]
+    fn pixel_centers_on_top_and_left_edges_are_filled() {
+        // a square with its corners on pixel centers, so every edge runs
+        // through a row or column of them
+        let (low, high) = (0.5, 3.5);
+        let triangles = [[[low, low], [high, low], [high, high]], [[low, low], [high, high], [low, high]]];
+        let counts = coverage(5, 5, &triangles);
+        for (y, row) in counts.iter().enumerate() {
+            for (x, &count) in row.iter().enumerate() {
+                // the left edge is x = 0 and the top edge is y = 3, while the
+                // bottom edge y = 0 and the right edge x = 3 are left out
+                let expected = (x <= 2 && (1..=3).contains(&y)) as u32;
+                assert_eq!(count, expected, "pixel {}, {}", x, y);
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use crate::matrix::{CurveType, Matrix};
+use std::f32;
+
+/// How a varied knob moves from its start value to its end value
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Easing{
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+    Cubic,
+    Exponential,
+    Sine,
+    Bounce,
+    /// a bezier curve from 0 to 1 with the two given control values
+    Bezier(f32, f32)
+}
+
+impl Easing{
+    /// the easing with the given name, as written after vary
+    pub fn from_name(name: &str) -> Option<Easing>{
+        match name{
+            "linear" => Some(Easing::Linear),
+            "ease_in" => Some(Easing::EaseIn),
+            "ease_out" => Some(Easing::EaseOut),
+            "ease_in_out" => Some(Easing::EaseInOut),
+            "cubic" => Some(Easing::Cubic),
+            "exponential" => Some(Easing::Exponential),
+            "sine" => Some(Easing::Sine),
+            "bounce" => Some(Easing::Bounce),
+            _ => None
+        }
+    }
+
+    /// Inputs:   t: how far through the vary, from 0 to 1
+    ///
+    /// Returns: how far the knob value should be from its start value
+    /// to its end value, where 0 is the start and 1 is the end
+    pub fn apply(&self, t: f32) -> f32{
+        match self{
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(2),
+            Easing::EaseInOut => {
+                if t < 0.5 {2.0 * t * t} else {1.0 - (-2.0 * t + 2.0).powi(2) / 2.0}
+            }
+            Easing::Cubic => {
+                if t < 0.5 {4.0 * t.powi(3)} else {1.0 - (-2.0 * t + 2.0).powi(3) / 2.0}
+            }
+            Easing::Exponential => {
+                if t <= 0.0 {
+                    0.0
+                }else if t >= 1.0 {
+                    1.0
+                }else if t < 0.5 {
+                    2.0_f32.powf(20.0 * t - 10.0) / 2.0
+                }else{
+                    (2.0 - 2.0_f32.powf(-20.0 * t + 10.0)) / 2.0
+                }
+            }
+            Easing::Sine => -((f32::consts::PI * t).cos() - 1.0) / 2.0,
+            Easing::Bounce => bounce_out(t),
+            Easing::Bezier(p1, p2) => {
+                let coefs = Matrix::generate_curve_coefs(0.0, *p1, *p2, 1.0, &CurveType::Bezier);
+                coefs.matrix_array[0][0] * t.powi(3)
+                    + coefs.matrix_array[1][0] * t.powi(2)
+                    + coefs.matrix_array[2][0] * t
+                    + coefs.matrix_array[3][0]
+            }
+        }
+    }
+}
+
+/// a ball dropped from 1 bouncing to rest at 0, flipped so it ends at 1
+fn bounce_out(t: f32) -> f32{
+    let strength = 7.5625;
+    let width = 2.75;
+    if t < 1.0 / width {
+        strength * t * t
+    }else if t < 2.0 / width {
+        let t = t - 1.5 / width;
+        strength * t * t + 0.75
+    }else if t < 2.5 / width {
+        let t = t - 2.25 / width;
+        strength * t * t + 0.9375
+    }else{
+        let t = t - 2.625 / width;
+        strength * t * t + 0.984375
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    const EASINGS: [Easing; 9] = [
+        Easing::Linear,
+        Easing::EaseIn,
+        Easing::EaseOut,
+        Easing::EaseInOut,
+        Easing::Cubic,
+        Easing::Exponential,
+        Easing::Sine,
+        Easing::Bounce,
+        Easing::Bezier(0.25, 1.5),
+    ];
+
+    #[test]
+    fn every_easing_starts_at_0_and_ends_at_1(){
+        for easing in EASINGS{
+            assert!(easing.apply(0.0).abs() < 1e-6, "{:?} starts at {}", easing, easing.apply(0.0));
+            assert!((easing.apply(1.0) - 1.0).abs() < 1e-6, "{:?} ends at {}", easing, easing.apply(1.0));
+        }
+    }
+
+    #[test]
+    fn symmetric_easings_are_halfway_in_the_middle(){
+        for easing in [Easing::Linear, Easing::EaseInOut, Easing::Cubic, Easing::Exponential, Easing::Sine]{
+            assert!((easing.apply(0.5) - 0.5).abs() < 1e-6, "{:?} is at {}", easing, easing.apply(0.5));
+        }
+    }
+
+    #[test]
+    fn every_name_is_read(){
+        for name in ["linear", "ease_in", "ease_out", "ease_in_out", "cubic", "exponential", "sine", "bounce"]{
+            assert!(Easing::from_name(name).is_some(), "{}", name);
+        }
+        assert_eq!(Easing::from_name("wobble"), None);
+    }
+}
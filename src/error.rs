@@ -0,0 +1,179 @@
+use pest::iterators::Pair;
+use pest::RuleType;
+use std::error::Error;
+use std::fmt;
+
+/// Where a command starts in a script, counting from 1
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn of<R: RuleType>(pair: &Pair<R>) -> Span {
+        let (line, column) = pair.as_span().start_pos().line_col();
+        Span { line, column }
+    }
+}
+
+/// Something in a script that a diagnostic can point at
+pub trait Located {
+    fn span(&self) -> Option<Span>;
+    /// the text of the command
+    fn source(&self) -> &str;
+}
+
+impl<R: RuleType> Located for Pair<'_, R> {
+    fn span(&self) -> Option<Span> {
+        Some(Span::of(self))
+    }
+
+    fn source(&self) -> &str {
+        self.as_str()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// Something wrong with a script, along with the command it was found at
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub span: Option<Span>,
+    /// the text of the command
+    pub source: String,
+}
+
+impl Diagnostic {
+    /// Returns: an error for a line of script that does not match the MDL grammar
+    pub fn from_pest<R: RuleType>(error: &pest::error::Error<R>, script: &str) -> Diagnostic {
+        let (line, column) = match error.line_col {
+            pest::error::LineColLocation::Pos(position) => position,
+            pest::error::LineColLocation::Span(start, _) => start,
+        };
+        Diagnostic {
+            severity: Severity::Error,
+            message: String::from("this is not a valid command"),
+            span: Some(Span { line, column }),
+            source: script.lines().nth(line - 1).unwrap_or("").to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.severity {
+            Severity::Error => write!(f, "ERROR: {}", self.message)?,
+            Severity::Warning => write!(f, "WARNING: {}", self.message)?,
+        }
+        if let Some(span) = self.span {
+            write!(f, " at line {} column {}", span.line, span.column)?;
+        }
+        if !self.source.is_empty() {
+            write!(f, ": {}", self.source.trim())?;
+        }
+        Ok(())
+    }
+}
+
+/// Collects the diagnostics of a script. A command that is drawn in every
+/// frame only reports each problem once
+#[derive(Clone, Debug, Default)]
+pub struct Diagnostics {
+    pub list: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Diagnostics {
+        Diagnostics { list: Vec::new() }
+    }
+
+    pub fn add(&mut self, diagnostic: Diagnostic) {
+        if !self.list.contains(&diagnostic) {
+            self.list.push(diagnostic);
+        }
+    }
+
+    pub fn error<L: Located>(&mut self, at: &L, message: String) {
+        self.add(Diagnostic {
+            severity: Severity::Error,
+            message,
+            span: at.span(),
+            source: at.source().to_owned(),
+        });
+    }
+
+    pub fn warning<L: Located>(&mut self, at: &L, message: String) {
+        self.add(Diagnostic {
+            severity: Severity::Warning,
+            message,
+            span: at.span(),
+            source: at.source().to_owned(),
+        });
+    }
+
+    /// an error that does not belong to any one command
+    pub fn general_error(&mut self, message: String) {
+        self.add(Diagnostic {
+            severity: Severity::Error,
+            message,
+            span: None,
+            source: String::new(),
+        });
+    }
+
+    pub fn general_warning(&mut self, message: String) {
+        self.add(Diagnostic {
+            severity: Severity::Warning,
+            message,
+            span: None,
+            source: String::new(),
+        });
+    }
+
+    /// adds the diagnostics found somewhere else, such as while drawing a frame on another thread
+    pub fn extend(&mut self, other: Diagnostics) {
+        for diagnostic in other.list {
+            self.add(diagnostic);
+        }
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.list.iter().any(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+}
+
+/// Why a script could not be rendered, with every diagnostic found in it
+#[derive(Clone, Debug)]
+pub struct MdlError {
+    pub file_name: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl MdlError {
+    pub fn new(file_name: &str, diagnostics: Diagnostics) -> MdlError {
+        MdlError {
+            file_name: file_name.to_owned(),
+            diagnostics: diagnostics.list,
+        }
+    }
+}
+
+impl fmt::Display for MdlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let errors = self.diagnostics.iter().filter(|diagnostic| diagnostic.severity == Severity::Error).count();
+        write!(f, "unable to render {} because of {} error{}", self.file_name, errors, if errors == 1 { "" } else { "s" })?;
+        for diagnostic in self.diagnostics.iter() {
+            write!(f, "\n{}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for MdlError {}
@@ -0,0 +1,439 @@
+//! An animated GIF89a encoder for 8 bit RGB frames.
+//!
+//! Every frame gets its own palette of at most 256 colors, chosen with
+//! median cut, and is optionally Floyd-Steinberg dithered before being
+//! LZW compressed. Frames are written as soon as they are added, so an
+//! animation never has to be held in memory.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+const MAX_COLORS: usize = 256;
+const MAX_CODE_SIZE: u32 = 12;
+
+/// How an animation is played back
+#[derive(Clone, Copy, Debug)]
+pub struct GifOptions {
+    /// time between frames in hundredths of a second
+    pub delay: u16,
+    /// how many times the animation plays, where 0 repeats it forever
+    pub loop_count: u16,
+    /// whether frames with more than 256 colors are dithered
+    pub dither: bool,
+}
+
+impl Default for GifOptions {
+    fn default() -> GifOptions {
+        GifOptions {
+            delay: 2,
+            loop_count: 0,
+            dither: false,
+        }
+    }
+}
+
+pub struct GifWriter<W: Write> {
+    writer: W,
+    width: usize,
+    height: usize,
+    options: GifOptions,
+}
+
+impl GifWriter<BufWriter<File>> {
+    /// Creates the file and writes the header of the animation
+    pub fn create(file_name: &str, width: usize, height: usize, options: GifOptions) -> io::Result<GifWriter<BufWriter<File>>> {
+        check_size(width, height)?;
+        let file = File::create(file_name)?;
+        GifWriter::new(BufWriter::new(file), width, height, options)
+    }
+}
+
+impl<W: Write> GifWriter<W> {
+    /// Writes the header of the animation.
+    /// Returns: an error if the gif can not be that big
+    pub fn new(mut writer: W, width: usize, height: usize, options: GifOptions) -> io::Result<GifWriter<W>> {
+        check_size(width, height)?;
+        writer.write_all(b"GIF89a")?;
+        writer.write_all(&(width as u16).to_le_bytes())?;
+        writer.write_all(&(height as u16).to_le_bytes())?;
+        // no global color table, since every frame has its own palette
+        writer.write_all(&[0, 0, 0])?;
+        // the netscape extension is what makes viewers repeat the animation,
+        // and its count is the number of repeats after the first time
+        if options.loop_count != 1 {
+            writer.write_all(&[0x21, 0xff, 11])?;
+            writer.write_all(b"NETSCAPE2.0")?;
+            writer.write_all(&[3, 1])?;
+            writer.write_all(&options.loop_count.saturating_sub(1).to_le_bytes())?;
+            writer.write_all(&[0])?;
+        }
+        Ok(GifWriter {
+            writer,
+            width,
+            height,
+            options,
+        })
+    }
+
+    /// Inputs: the colors of a frame as 3 bytes per pixel, row by row from the top
+    pub fn add_frame(&mut self, rgb: &[u8]) -> io::Result<()> {
+        let (palette, indices) = quantize(self.width, self.height, rgb, self.options.dither);
+        let mut bits = 1;
+        while (1 << bits) < palette.len() {
+            bits += 1;
+        }
+
+        // graphic control extension with the delay, leaving each frame in place
+        self.writer.write_all(&[0x21, 0xf9, 4, 0b0000_0100])?;
+        self.writer.write_all(&self.options.delay.to_le_bytes())?;
+        self.writer.write_all(&[0, 0])?;
+
+        // image descriptor covering the whole canvas, with a local color table
+        self.writer.write_all(&[0x2c, 0, 0, 0, 0])?;
+        self.writer.write_all(&(self.width as u16).to_le_bytes())?;
+        self.writer.write_all(&(self.height as u16).to_le_bytes())?;
+        self.writer.write_all(&[0x80 | (bits - 1) as u8])?;
+        for i in 0..1 << bits {
+            let color = palette.get(i).unwrap_or(&[0, 0, 0]);
+            self.writer.write_all(color)?;
+        }
+
+        let min_code_size = bits.max(2);
+        self.writer.write_all(&[min_code_size as u8])?;
+        for block in lzw_compress(&indices, min_code_size).chunks(255) {
+            self.writer.write_all(&[block.len() as u8])?;
+            self.writer.write_all(block)?;
+        }
+        self.writer.write_all(&[0])?;
+        Ok(())
+    }
+
+    /// Writes the trailer and returns the underlying writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.write_all(&[0x3b])?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// the size of a gif is stored in 16 bits
+fn check_size(width: usize, height: usize) -> io::Result<()> {
+    if width > u16::MAX as usize || height > u16::MAX as usize {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("a gif can be at most {} pixels wide and tall", u16::MAX)));
+    }
+    Ok(())
+}
+
+/// Returns: the palette of a frame and the palette index of every pixel
+fn quantize(width: usize, height: usize, rgb: &[u8], dither: bool) -> (Vec<[u8; 3]>, Vec<u8>) {
+    let mut histogram: HashMap<[u8; 3], u32> = HashMap::new();
+    for pixel in rgb.chunks(3) {
+        *histogram.entry([pixel[0], pixel[1], pixel[2]]).or_insert(0) += 1;
+    }
+
+    // a frame that already fits in a palette is stored exactly
+    if histogram.len() <= MAX_COLORS {
+        let palette: Vec<[u8; 3]> = histogram.keys().copied().collect();
+        let lookup: HashMap<[u8; 3], u8> = palette.iter().enumerate().map(|(i, &color)| (color, i as u8)).collect();
+        let indices = rgb.chunks(3).map(|pixel| lookup[&[pixel[0], pixel[1], pixel[2]]]).collect();
+        return (palette, indices);
+    }
+
+    let palette = median_cut(histogram.into_iter().collect(), MAX_COLORS);
+    let mut indices = Vec::with_capacity(width * height);
+    if dither {
+        // nearest colors are looked up by their top 5 bits, since dithering
+        // makes almost every pixel a different color
+        let mut nearest_table = vec![u16::MAX; 1 << 15];
+        let mut errors = vec![[0.0f32; 3]; width + 2];
+        let mut next_errors = vec![[0.0f32; 3]; width + 2];
+        for y in 0..height {
+            for x in 0..width {
+                let pixel = &rgb[(y * width + x) * 3..(y * width + x) * 3 + 3];
+                let mut color = [0u8; 3];
+                for channel in 0..3 {
+                    color[channel] = (pixel[channel] as f32 + errors[x + 1][channel]).round().clamp(0.0, 255.0) as u8;
+                }
+                let key = (color[0] as usize >> 3) << 10 | (color[1] as usize >> 3) << 5 | color[2] as usize >> 3;
+                if nearest_table[key] == u16::MAX {
+                    nearest_table[key] = nearest(&palette, color) as u16;
+                }
+                let index = nearest_table[key] as usize;
+                indices.push(index as u8);
+                // spread the error to the right and to the row below
+                for channel in 0..3 {
+                    let error = color[channel] as f32 - palette[index][channel] as f32;
+                    errors[x + 2][channel] += error * 7.0 / 16.0;
+                    next_errors[x][channel] += error * 3.0 / 16.0;
+                    next_errors[x + 1][channel] += error * 5.0 / 16.0;
+                    next_errors[x + 2][channel] += error / 16.0;
+                }
+            }
+            std::mem::swap(&mut errors, &mut next_errors);
+            next_errors.iter_mut().for_each(|error| *error = [0.0; 3]);
+        }
+    } else {
+        let mut cache: HashMap<[u8; 3], u8> = HashMap::new();
+        for pixel in rgb.chunks(3) {
+            let color = [pixel[0], pixel[1], pixel[2]];
+            let index = *cache.entry(color).or_insert_with(|| nearest(&palette, color) as u8);
+            indices.push(index);
+        }
+    }
+    (palette, indices)
+}
+
+/// Returns: the index of the palette color closest to color
+fn nearest(palette: &[[u8; 3]], color: [u8; 3]) -> usize {
+    let mut best = 0;
+    let mut best_distance = i32::MAX;
+    for (i, entry) in palette.iter().enumerate() {
+        let distance: i32 = (0..3).map(|channel| (entry[channel] as i32 - color[channel] as i32).pow(2)).sum();
+        if distance < best_distance {
+            best_distance = distance;
+            best = i;
+        }
+    }
+    best
+}
+
+/// Splits the colors into boxes, always cutting the box with the widest
+/// range of one channel at its median along that channel, until there
+/// are max_colors boxes. Returns: the average color of each box
+fn median_cut(colors: Vec<([u8; 3], u32)>, max_colors: usize) -> Vec<[u8; 3]> {
+    let mut boxes = vec![colors];
+    while boxes.len() < max_colors {
+        let mut widest = None;
+        let mut widest_range = 0;
+        for (i, colors) in boxes.iter().enumerate() {
+            if colors.len() < 2 {
+                continue;
+            }
+            let (channel, range) = widest_channel(colors);
+            if range > widest_range {
+                widest_range = range;
+                widest = Some((i, channel));
+            }
+        }
+        let (i, channel) = match widest {
+            Some(widest) => widest,
+            None => break,
+        };
+
+        let mut colors = boxes.swap_remove(i);
+        colors.sort_unstable_by_key(|(color, _)| color[channel]);
+        let total: u32 = colors.iter().map(|(_, count)| count).sum();
+        let mut seen = 0;
+        let mut split = 1;
+        for (j, (_, count)) in colors.iter().enumerate() {
+            seen += count;
+            if seen * 2 >= total {
+                split = j + 1;
+                break;
+            }
+        }
+        // both halves need at least one color
+        split = split.clamp(1, colors.len() - 1);
+        let upper = colors.split_off(split);
+        boxes.push(colors);
+        boxes.push(upper);
+    }
+
+    boxes
+        .iter()
+        .map(|colors| {
+            let total: u64 = colors.iter().map(|&(_, count)| count as u64).sum();
+            let mut sum = [0u64; 3];
+            for (color, count) in colors.iter() {
+                for channel in 0..3 {
+                    sum[channel] += color[channel] as u64 * *count as u64;
+                }
+            }
+            [(sum[0] / total) as u8, (sum[1] / total) as u8, (sum[2] / total) as u8]
+        })
+        .collect()
+}
+
+/// Returns: the channel with the largest range of values and that range
+fn widest_channel(colors: &[([u8; 3], u32)]) -> (usize, u8) {
+    let mut widest = (0, 0);
+    for channel in 0..3 {
+        let min = colors.iter().map(|(color, _)| color[channel]).min().unwrap();
+        let max = colors.iter().map(|(color, _)| color[channel]).max().unwrap();
+        if max - min > widest.1 {
+            widest = (channel, max - min);
+        }
+    }
+    widest
+}
+
+/// Writes variable width codes, least significant bit first
+struct CodeWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl CodeWriter {
+    fn write(&mut self, code: u16, size: u32) {
+        self.buffer |= (code as u32) << self.count;
+        self.count += size;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+}
+
+/// Returns: the palette indices compressed with GIF's variant of LZW
+fn lzw_compress(indices: &[u8], min_code_size: u32) -> Vec<u8> {
+    let clear_code: u16 = 1 << min_code_size;
+    let end_code = clear_code + 1;
+    let mut writer = CodeWriter { bytes: Vec::new(), buffer: 0, count: 0 };
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut code_size = min_code_size + 1;
+    let mut next_code = end_code + 1;
+
+    writer.write(clear_code, code_size);
+    let mut prefix = match indices.first() {
+        Some(&index) => index as u16,
+        None => {
+            writer.write(end_code, code_size);
+            writer.write(0, 7);
+            return writer.bytes;
+        }
+    };
+    for &index in &indices[1..] {
+        if let Some(&code) = table.get(&(prefix, index)) {
+            prefix = code;
+            continue;
+        }
+        writer.write(prefix, code_size);
+        if next_code < 1 << MAX_CODE_SIZE {
+            table.insert((prefix, index), next_code);
+            // the decoder widens its codes as soon as the next code needs another bit
+            if next_code == 1 << code_size {
+                code_size += 1;
+            }
+            next_code += 1;
+        } else {
+            // the table is full, so start over
+            writer.write(clear_code, code_size);
+            table.clear();
+            code_size = min_code_size + 1;
+            next_code = end_code + 1;
+        }
+        prefix = index as u16;
+    }
+    writer.write(prefix, code_size);
+    writer.write(end_code, code_size);
+    // flush the last partial byte
+    writer.write(0, 7);
+    writer.bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a plain GIF LZW decoder, to check that the compressed codes read back
+    fn lzw_decompress(bytes: &[u8], min_code_size: u32) -> Vec<u8> {
+        let clear_code = 1 << min_code_size;
+        let end_code = clear_code + 1;
+        let reset = || -> Vec<Vec<u8>> {
+            let mut table: Vec<Vec<u8>> = (0..clear_code).map(|index| vec![index as u8]).collect();
+            table.extend([Vec::new(), Vec::new()]);
+            table
+        };
+        let mut table = reset();
+        let mut code_size = min_code_size + 1;
+        let mut previous: Option<usize> = None;
+        let mut output = Vec::new();
+        let (mut buffer, mut count, mut position) = (0u32, 0u32, 0);
+        loop {
+            while count < code_size {
+                buffer |= (*bytes.get(position).expect("the codes ended without an end code") as u32) << count;
+                position += 1;
+                count += 8;
+            }
+            let code = (buffer & ((1 << code_size) - 1)) as usize;
+            buffer >>= code_size;
+            count -= code_size;
+            if code == clear_code {
+                table = reset();
+                code_size = min_code_size + 1;
+                previous = None;
+                continue;
+            }
+            if code == end_code {
+                return output;
+            }
+            let entry = match previous {
+                None => table[code].clone(),
+                Some(previous) => {
+                    let entry = if code < table.len() {
+                        table[code].clone()
+                    } else {
+                        assert_eq!(code, table.len(), "a code that is not in the table yet");
+                        let mut entry = table[previous].clone();
+                        entry.push(entry[0]);
+                        entry
+                    };
+                    if table.len() < 1 << MAX_CODE_SIZE {
+                        let mut new_entry = table[previous].clone();
+                        new_entry.push(entry[0]);
+                        table.push(new_entry);
+                        if table.len() == 1 << code_size && code_size < MAX_CODE_SIZE {
+                            code_size += 1;
+                        }
+                    }
+                    entry
+                }
+            };
+            output.extend_from_slice(&entry);
+            previous = Some(code);
+        }
+    }
+
+    fn assert_round_trips(indices: &[u8], min_code_size: u32) {
+        let decoded = lzw_decompress(&lzw_compress(indices, min_code_size), min_code_size);
+        assert_eq!(decoded, indices);
+    }
+
+    #[test]
+    fn canvas_too_big_for_a_gif_is_an_error() {
+        assert!(GifWriter::new(Vec::new(), u16::MAX as usize, 10, GifOptions::default()).is_ok());
+        assert_eq!(GifWriter::new(Vec::new(), 70000, 10, GifOptions::default()).err().unwrap().kind(), io::ErrorKind::InvalidInput);
+        assert!(GifWriter::new(Vec::new(), 10, 70000, GifOptions::default()).is_err());
+        // the file is not created either
+        let file_name = std::env::temp_dir().join(format!("hw_11_too_big_{}.gif", std::process::id())).to_string_lossy().into_owned();
+        assert!(GifWriter::create(&file_name, 70000, 70000, GifOptions::default()).is_err());
+        assert!(!std::path::Path::new(&file_name).exists());
+    }
+
+    #[test]
+    fn lzw_round_trips_short_inputs() {
+        assert_round_trips(&[], 2);
+        assert_round_trips(&[3], 2);
+        assert_round_trips(&[0, 1, 2, 3, 0, 1, 2, 3, 0, 1], 2);
+        // a run makes the encoder use a code the decoder has not added yet
+        assert_round_trips(&[1; 50], 2);
+    }
+
+    #[test]
+    fn lzw_round_trips_past_a_full_table() {
+        // varied enough that the table fills up and is cleared several times
+        let mut state: u32 = 12345;
+        let indices: Vec<u8> = (0..200_000)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect();
+        assert_round_trips(&indices, 8);
+        let small: Vec<u8> = indices.iter().map(|index| index & 3).collect();
+        assert_round_trips(&small, 2);
+    }
+}
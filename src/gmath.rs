@@ -1,46 +1,47 @@
 use crate::Color;
+use crate::lighting::{Constants, Light, SpecularModel};
 use crate::Matrix;
-use crate::ReflectionValue;
-use std::cmp;
+use crate::reflect::ReflectionValue;
+use std::collections::HashMap;
 use std::ops::Add;
 
 //vector functions
 //normalize vector, should modify the parameter
-pub fn normalize(vector: &mut Vec<f32>) {
+pub fn normalize(vector: &mut [f32]) {
   let mut m = 0.0;
-  for i in 0..vector.len() {
-    m += vector[i].powi(2);
+  for value in vector.iter() {
+    m += value.powi(2);
   }
   m = m.sqrt();
-  for i in 0..vector.len() {
-    vector[i] /= m;
+  for value in vector.iter_mut() {
+    *value /= m;
   }
 }
 
 // Return the dot product of a . b
-pub fn dot_product(lhs: &Vec<f32>, rhs: &Vec<f32>) -> f32 {
+pub fn dot_product(lhs: &[f32], rhs: &[f32]) -> f32 {
   let mut result = 0.0;
-  for i in 0..cmp::min(lhs.len(), rhs.len()) {
-    result += lhs[i] * rhs[i];
+  for (l, r) in lhs.iter().zip(rhs.iter()) {
+    result += l * r;
   }
-  return result;
+  result
 }
 
 // modify the param vector when the vector is multiplied by a scalar
-pub fn vector_times_scalar(vector: &mut Vec<f32>, scalar: f32) -> Vec<f32> {
-  let mut result = vector.clone();
-  for i in 0..result.len() {
-    result[i] *= scalar;
+pub fn vector_times_scalar(vector: &[f32], scalar: f32) -> Vec<f32> {
+  let mut result = vector.to_vec();
+  for value in result.iter_mut() {
+    *value *= scalar;
   }
-  return result;
+  result
 }
 
-pub fn vector_subtraction(lhs: &mut Vec<f32>, rhs: &mut Vec<f32>) -> Vec<f32> {
-  let mut result = lhs.clone();
-  for i in 0..result.len() {
-    result[i] -= rhs[i];
+pub fn vector_subtraction(lhs: &[f32], rhs: &[f32]) -> Vec<f32> {
+  let mut result = lhs.to_vec();
+  for (l, r) in result.iter_mut().zip(rhs.iter()) {
+    *l -= r;
   }
-  return result;
+  result
 }
 
 impl Matrix {
@@ -62,9 +63,87 @@ impl Matrix {
     let bx = x2 - x0;
     let by = y2 - y0;
     let bz = z2 - z0;
-    return vec![ay * bz - az * by, az * bx - ax * bz, ax * by - ay * bx];
+    vec![ay * bz - az * by, az * bx - ax * bz, ax * by - ay * bx]
     // return dot_product(&n, &consts::view);
   }
+
+  //Weld together the points of polygons that are within epsilon of
+  //each other, and give each welded vertex a normal made by adding up
+  //the surface normals of every triangle that shares it
+  pub fn weld_vertices(&self, epsilon: f32) -> WeldedPolygons {
+    let mut welded = WeldedPolygons {
+      vertices: Vec::new(),
+      triangles: Vec::new(),
+      normals: Vec::new(),
+    };
+    // vertices are bucketed into cubes of side epsilon, so a match can only
+    // be in the same cube or one of its neighbours
+    let mut grid: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+    let cell = |point: &[f32; 3]| point.map(|value| (value / epsilon).floor() as i64);
+    for i in (0..self.matrix_array[0].len()).step_by(3) {
+      let normal = self.calculate_normal(i);
+      let mut triangle = [0; 3];
+      for (corner, point_index) in (i..i + 3).enumerate() {
+        let point = [0, 1, 2].map(|axis| self.matrix_array[axis][point_index]);
+        let point_cell = cell(&point);
+        let mut found = None;
+        'search: for dx in -1..=1 {
+          for dy in -1..=1 {
+            for dz in -1..=1 {
+              let neighbour = [point_cell[0] + dx, point_cell[1] + dy, point_cell[2] + dz];
+              if let Some(candidates) = grid.get(&neighbour) {
+                for &vertex in candidates {
+                  let other = welded.vertices[vertex];
+                  if (0..3).all(|axis| (other[axis] - point[axis]).abs() <= epsilon) {
+                    found = Some(vertex);
+                    break 'search;
+                  }
+                }
+              }
+            }
+          }
+        }
+        let vertex = match found {
+          Some(vertex) => vertex,
+          None => {
+            welded.vertices.push(point);
+            welded.normals.push(vec![0.0; 3]);
+            grid.entry(point_cell).or_default().push(welded.vertices.len() - 1);
+            welded.vertices.len() - 1
+          }
+        };
+        for (sum, value) in welded.normals[vertex].iter_mut().zip(normal.iter()) {
+          *sum += value;
+        }
+        triangle[corner] = vertex;
+      }
+      welded.triangles.push(triangle);
+    }
+    for normal in welded.normals.iter_mut() {
+      if normal.iter().any(|&value| value != 0.0) {
+        normalize(normal);
+      }
+    }
+    welded
+  }
+}
+
+/// A polygon matrix with its coincident points welded into shared vertices.
+///
+/// triangles has one entry per triangle of the polygon matrix, in the same
+/// order, holding the indices of its three vertices. normals holds the
+/// normal of each vertex.
+pub struct WeldedPolygons {
+  pub vertices: Vec<[f32; 3]>,
+  pub triangles: Vec<[usize; 3]>,
+  pub normals: Vec<Vec<f32>>,
+}
+
+impl WeldedPolygons {
+  //the vertex normal of the point at index i in the polygon matrix
+  pub fn point_normal(&self, i: usize) -> &Vec<f32> {
+    &self.normals[self.triangles[i / 3][i % 3]]
+  }
 }
 
 impl Color {
@@ -73,9 +152,9 @@ impl Color {
     let g: u8 = if constant * light_color.g as f32 * reflect.g > 255.0 {255} else{(constant * light_color.g as f32 * reflect.g) as u8};
     let b: u8 = if constant * light_color.b as f32 * reflect.b > 255.0 {255} else{(constant * light_color.b as f32 * reflect.b) as u8};
     Color {
-      r: r,
-      g: g,
-      b: b,
+      r,
+      g,
+      b,
     }
   }
 }
@@ -88,9 +167,9 @@ impl Add for Color {
     let g: u8 = if self.g as i32 + other.g as i32 > 255 {255} else{self.g + other.g};
     let b: u8 = if self.b as i32 + other.b as i32 > 255 {255} else{self.b + other.b};
     Self {
-      r: r,
-      g: g,
-      b: b,
+      r,
+      g,
+      b,
     }
   }
 }
@@ -100,78 +179,214 @@ IMPORTANT NOTE
 
 Ambient light is represented by a color value
 
-Point light sources are 2D arrays of doubles.
-     - The fist index (LOCATION) represents the vector to the light.
-     - The second index (COLOR) represents the color.
+Point light sources are Light values.
+     - location represents the vector to the light.
+     - color represents the color.
+     - every light's diffuse and specular terms are summed.
 
 Reflection constants (ka, kd, ks) are represented as arrays of
 doubles (red, green, blue)
+
+The specular term is raised to the shininess of the surface, using
+either the reflection of the light (Phong) or the vector halfway
+between the light and the view (Blinn-Phong)
+
+The intrinsic color of a surface is added on top of the light it
+reflects, so it shows up even with no light pointed at it
 ============================================*/
 
 //lighting functions
 
 pub fn get_lighting(
-  normal: &mut Vec<f32>,
-  view: &mut Vec<f32>,
+  normal: &mut [f32],
+  view: &mut [f32],
   ambient_light: &Color,
-  point_light_color: &Color,
-  point_light_vector: &mut Vec<f32>,
-  ambient_reflect: &ReflectionValue,
-  diffuse_reflect: &ReflectionValue,
-  specular_reflect: &ReflectionValue,
+  lights: &[Light],
+  constants: &Constants,
+  specular_model: SpecularModel,
 ) -> Color {
   normalize(normal);
-  normalize(point_light_vector);
-  let ambient_color = calculate_ambient(ambient_light, ambient_reflect);
-  let diffuse_color = calculate_diffuse(
-    point_light_vector,
-    point_light_color,
-    diffuse_reflect,
-    normal,
-  );
-  let specular_color = calculate_specular(
-    point_light_vector,
-    point_light_color,
-    specular_reflect,
-    view,
-    normal,
-  );
-  return ambient_color + diffuse_color + specular_color;
+  let mut color = constants.intrinsic_color() + calculate_ambient(ambient_light, &constants.ambient_reflect);
+  for light in lights {
+    let point_light_vector = &mut light.location.clone();
+    normalize(point_light_vector);
+    let diffuse_color = calculate_diffuse(
+      point_light_vector,
+      &light.color,
+      &constants.diffuse_reflect,
+      normal,
+    );
+    let specular_color = calculate_specular(
+      point_light_vector,
+      &light.color,
+      &constants.specular_reflect,
+      view,
+      normal,
+      constants.shininess,
+      specular_model,
+    );
+    color = color + diffuse_color + specular_color;
+  }
+  color
 }
 
 pub fn calculate_ambient(ambient_light: &Color, ambient_reflect: &ReflectionValue) -> Color {
-  return Color::color_with_lighting(1.0, ambient_light, ambient_reflect);
+  Color::color_with_lighting(1.0, ambient_light, ambient_reflect)
 }
 
 pub fn calculate_diffuse(
-  normalized_diffuse_light_vector: &mut Vec<f32>,
+  normalized_diffuse_light_vector: &[f32],
   diffuse_light_color: &Color,
   diffuse_reflect: &ReflectionValue,
-  normalized_normal: &mut Vec<f32>,
+  normalized_normal: &[f32],
 ) -> Color {
-  let n_l_dot_product_times = dot_product(normalized_normal, normalized_diffuse_light_vector);
-  return Color::color_with_lighting(n_l_dot_product_times, diffuse_light_color, diffuse_reflect);
+  // a light behind the surface does not light it at all
+  let n_l_dot_product_times = dot_product(normalized_normal, normalized_diffuse_light_vector).max(0.0);
+  Color::color_with_lighting(n_l_dot_product_times, diffuse_light_color, diffuse_reflect)
 }
 
 pub fn calculate_specular(
-  normalized_specular_light_vector: &mut Vec<f32>,
+  normalized_specular_light_vector: &[f32],
   specular_light_color: &Color,
   specular_reflect: &ReflectionValue,
-  view: &mut Vec<f32>,
-  normalized_normal: &mut Vec<f32>,
+  view: &mut [f32],
+  normalized_normal: &[f32],
+  shininess: f32,
+  specular_model: SpecularModel,
 ) -> Color {
-  let calculation_before_color_and_light = &mut vector_subtraction(
-    &mut vector_times_scalar(
-      normalized_normal,
-      2.0 * dot_product(normalized_normal, normalized_specular_light_vector),
-    ),
-    normalized_specular_light_vector,
-  );
+  let n_l_dot_product = dot_product(normalized_normal, normalized_specular_light_vector);
+  // a light behind the surface gives it no highlight
+  if n_l_dot_product <= 0.0 {
+    return Color::new_color(0, 0, 0);
+  }
   normalize(view);
-  let calculation_before_color = dot_product(calculation_before_color_and_light, view);
-  return Color::color_with_lighting(
+  let calculation_before_shininess = match specular_model {
+    SpecularModel::Phong => {
+      let reflection = &vector_subtraction(
+        &vector_times_scalar(normalized_normal, 2.0 * n_l_dot_product),
+        normalized_specular_light_vector,
+      );
+      dot_product(reflection, view)
+    }
+    SpecularModel::BlinnPhong => {
+      let halfway: &mut Vec<f32> = &mut normalized_specular_light_vector.iter().zip(view.iter()).map(|(l, v)| l + v).collect();
+      normalize(halfway);
+      dot_product(normalized_normal, halfway)
+    }
+  };
+  let calculation_before_color = calculation_before_shininess.max(0.0).powf(shininess);
+  Color::color_with_lighting(
     calculation_before_color,
     specular_light_color,
     specular_reflect,
-  );
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// a polygon matrix holding the given triangles
+  fn polygons(triangles: &[[[f32; 3]; 3]]) -> Matrix {
+    let mut matrix = Matrix::new(0, 0);
+    for [a, b, c] in triangles {
+      matrix.add_point(a[0], a[1], a[2]);
+      matrix.add_point(b[0], b[1], b[2]);
+      matrix.add_point(c[0], c[1], c[2]);
+    }
+    matrix
+  }
+
+  fn assert_close(actual: &[f32], expected: &[f32]) {
+    assert!(actual.iter().zip(expected).all(|(a, e)| (a - e).abs() < 1e-4), "{:?} is not {:?}", actual, expected);
+  }
+
+  #[test]
+  fn vertices_across_a_cell_boundary_are_welded() {
+    // an epsilon of 0.25 puts cell boundaries at 0 and 1, and the second
+    // triangle's copies of the shared edge are just on the other side of them
+    let welded = polygons(&[
+      [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+      [[-1e-6, 0.0, 0.0], [0.0, 0.0, 1.0], [0.999999, 0.0, 0.0]],
+    ])
+    .weld_vertices(0.25);
+    assert_eq!(welded.vertices.len(), 4);
+    assert_eq!(welded.triangles, vec![[0, 1, 2], [0, 3, 1]]);
+  }
+
+  #[test]
+  fn points_further_apart_than_epsilon_are_not_welded() {
+    let welded = polygons(&[[[0.0, 0.0, 0.0], [0.3, 0.0, 0.0], [0.0, 0.3, 0.0]]]).weld_vertices(0.25);
+    assert_eq!(welded.vertices.len(), 3);
+  }
+
+  #[test]
+  fn shared_vertices_average_the_normals_of_their_faces() {
+    // two faces at a right angle, one facing +z and the other +y
+    let welded = polygons(&[
+      [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+      [[0.0, 0.0, 0.0], [0.0, 0.0, 1.0], [1.0, 0.0, 0.0]],
+    ])
+    .weld_vertices(0.25);
+    let diagonal = 0.5_f32.sqrt();
+    for shared in [0, 1, 3, 5] {
+      assert_close(welded.point_normal(shared), &[0.0, diagonal, diagonal]);
+    }
+    assert_close(welded.point_normal(2), &[0.0, 0.0, 1.0]);
+    assert_close(welded.point_normal(4), &[0.0, 1.0, 0.0]);
+  }
+
+  /// the specular light for a light at 45 degrees to the normal, seen from
+  /// straight along the normal
+  fn specular_at_45_degrees(shininess: f32, specular_model: SpecularModel) -> Color {
+    let half = 0.5_f32.sqrt();
+    calculate_specular(
+      &[half, 0.0, half],
+      &Color::new_color(255, 255, 255),
+      &ReflectionValue::new_values(1.0, 1.0, 1.0),
+      &mut [0.0, 0.0, 1.0],
+      &[0.0, 0.0, 1.0],
+      shininess,
+      specular_model,
+    )
+  }
+
+  #[test]
+  fn phong_compares_the_reflection_with_the_view() {
+    // the light reflects to 45 degrees from the view, so the cosine is 0.707
+    assert_eq!(specular_at_45_degrees(1.0, SpecularModel::Phong), Color::new_color(180, 180, 180));
+    assert_eq!(specular_at_45_degrees(2.0, SpecularModel::Phong), Color::new_color(127, 127, 127));
+  }
+
+  #[test]
+  fn blinn_phong_compares_the_halfway_vector_with_the_normal() {
+    // the halfway vector is 22.5 degrees from the normal, so the cosine is 0.924
+    assert_eq!(specular_at_45_degrees(1.0, SpecularModel::BlinnPhong), Color::new_color(235, 235, 235));
+    assert_eq!(specular_at_45_degrees(2.0, SpecularModel::BlinnPhong), Color::new_color(217, 217, 217));
+  }
+
+  #[test]
+  fn intrinsic_color_is_added_to_the_reflected_light() {
+    let constants = Constants::new(0.5, 0.0, 0.0, 0.5, 0.0, 0.0, 0.5, 0.0, 0.0, 40.0, 80.0, 120.0);
+    let lit = |ambient_light: Color| get_lighting(&mut [0.0, 0.0, 1.0], &mut [0.0, 0.0, 1.0], &ambient_light, &[], &constants, SpecularModel::Phong);
+    // it shows up even in the dark
+    assert_eq!(lit(Color::new_color(0, 0, 0)), Color::new_color(40, 80, 120));
+    assert_eq!(lit(Color::new_color(100, 100, 100)), Color::new_color(90, 130, 170));
+  }
+
+  #[test]
+  fn light_behind_the_surface_has_no_highlight() {
+    for specular_model in [SpecularModel::Phong, SpecularModel::BlinnPhong] {
+      let color = calculate_specular(
+        &[0.0, 0.0, -1.0],
+        &Color::new_color(200, 200, 200),
+        &ReflectionValue::new_values(1.0, 1.0, 1.0),
+        &mut [0.0, 0.0, 1.0],
+        &[0.0, 0.0, 1.0],
+        1.0,
+        specular_model,
+      );
+      assert_eq!(color, Color::new_color(0, 0, 0));
+    }
+  }
 }
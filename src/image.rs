@@ -1,102 +1,381 @@
 use crate::color::Color;
+use crate::png::encode_png;
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
-use std::fs::File;
-use std::io::prelude::*;
+use std::fs::{self, File};
+use std::io::{self, prelude::*, BufReader, BufWriter};
 use std::path::Path;
 use std::process::Command;
 
+/// The two kinds of ppm file: P3 stores each channel as decimal text and
+/// P6 stores it as a single byte
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PpmFormat {
+    Ascii,
+    Binary,
+}
+
+/// Which of two things drawn on the same pixel is kept. z grows toward the
+/// camera, and a pixel nothing has been drawn on yet lets anything through
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DepthTest {
+    /// keeps the closest thing, which is the default
+    Greater,
+    /// keeps the furthest thing
+    Less,
+    /// keeps whatever was drawn last
+    Always,
+}
+
+impl DepthTest {
+    /// the depth test with the given name, as written after depth_test
+    pub fn from_name(name: &str) -> Option<DepthTest> {
+        match name {
+            "greater" => Some(DepthTest::Greater),
+            "less" => Some(DepthTest::Less),
+            "always" => Some(DepthTest::Always),
+            _ => None,
+        }
+    }
+
+    /// Inputs:   z: the depth of what is being drawn
+    ///
+    /// depth: the depth already in the z buffer
+    ///
+    /// Returns: whether it is drawn. Something at the same depth is, so
+    /// later things win ties
+    pub fn passes(&self, z: f32, depth: f32) -> bool {
+        match self {
+            DepthTest::Greater => z >= depth,
+            DepthTest::Less => depth == f32::NEG_INFINITY || z <= depth,
+            DepthTest::Always => true,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Image {
     pub screen: Vec<Vec<Color>>,
+    /// the depth of what is drawn on each pixel, or negative infinity
     pub z_buffer: Vec<Vec<f32>>,
     pub height: usize,
     pub width: usize,
+    pub depth_test: DepthTest,
+    /// added to the depth of everything plotted, so that lines drawn over a
+    /// surface at the same depth can be pulled in front of it
+    pub depth_bias: f32,
 }
 
 impl Image {
     pub fn new(image_width: usize, image_height: usize) -> Image {
         Image {
             screen: vec![vec![Color::new(); image_width]; image_height],
-            z_buffer: vec![vec![f32::MIN; image_width]; image_height],
+            z_buffer: vec![vec![f32::NEG_INFINITY; image_width]; image_height],
             width: image_width,
             height: image_height,
+            depth_test: DepthTest::Greater,
+            depth_bias: 0.0,
         }
     }
 
-    pub fn plot(&mut self, x: i32, y: i32, mut z: f32, color: &Color) -> bool{
-        z = (z as i32 * 10000) as f32 / 10000.0;
+    pub fn plot(&mut self, x: i32, y: i32, z: f32, color: &Color) -> bool{
         if x >= 0 && y >= 0 && x < self.width as i32 && y < self.height as i32{
-            if z >= self.z_buffer[(self.height - 1) - y as usize][x as usize] {
-                self.screen[(self.height - 1) - y as usize][x as usize].plot_color(color);
-                self.z_buffer[(self.height - 1) - y as usize][x as usize] = z;
+            let row = (self.height - 1) - y as usize;
+            let z = z + self.depth_bias;
+            if self.depth_test.passes(z, self.z_buffer[row][x as usize]) {
+                self.screen[row][x as usize].plot_color(color);
+                self.z_buffer[row][x as usize] = z;
             }
-            return true;
+            true
         }else{
-            return false;
+            false
         }
     }
 
-    fn create_data(&self) -> String {
-        let mut result: String =
-            format!("P3\n{} {}\n255\n", self.screen[0].len(), self.screen.len());
-
-        for i in 0..self.screen.len() {
-            for v in 0..self.screen[i].len() {
-                result.push_str(&self.screen[i][v].to_string().to_owned());
-                result.push_str("  ");
+    /// writes the image as a ppm file one row at a time
+    pub fn write_ppm<W: Write>(&self, writer: &mut W, format: PpmFormat) -> io::Result<()> {
+        match format {
+            PpmFormat::Ascii => write!(writer, "P3\n{} {}\n255\n", self.width, self.height)?,
+            PpmFormat::Binary => write!(writer, "P6\n{} {}\n255\n", self.width, self.height)?,
+        }
+        let mut row_bytes = Vec::with_capacity(self.width * 3);
+        for row in self.screen.iter() {
+            match format {
+                PpmFormat::Ascii => {
+                    for color in row.iter() {
+                        write!(writer, "{}  ", color)?;
+                    }
+                    writeln!(writer)?;
+                }
+                PpmFormat::Binary => {
+                    row_bytes.clear();
+                    for color in row.iter() {
+                        row_bytes.extend_from_slice(&[color.r, color.g, color.b]);
+                    }
+                    writer.write_all(&row_bytes)?;
+                }
             }
-            result.push_str("\n");
         }
-        return result;
+        writer.flush()
+    }
+
+    pub fn create_file(&self, file_name: &str, format: PpmFormat) -> io::Result<()> {
+        let file = File::create(Path::new(&file_name))?;
+        self.write_ppm(&mut BufWriter::new(file), format)
     }
 
-    pub fn create_file(&self, file_name: &str) {
-        let path = Path::new(&file_name);
+    /// Inputs: the name of a P3 or P6 ppm file
+    /// Returns: the image in the file
+    pub fn read_ppm(file_name: &str) -> io::Result<Image> {
+        let mut data = Vec::new();
+        BufReader::new(File::open(file_name)?).read_to_end(&mut data)?;
+        let mut reader = PpmReader { data: &data, position: 0 };
 
-        let mut file = match File::create(&path) {
-            Err(error) => panic!("failed to create image file because {}", error),
-            Ok(file) => file,
+        let format = match reader.token()? {
+            b"P3" => PpmFormat::Ascii,
+            b"P6" => PpmFormat::Binary,
+            _ => return Err(invalid_ppm("it is not a P3 or P6 file")),
         };
+        let width = reader.number()?;
+        let height = reader.number()?;
+        let max_value = reader.number()?;
+        if max_value == 0 || max_value > 255 {
+            return Err(invalid_ppm("only channels of at most 255 are supported"));
+        }
+        // a single whitespace character separates the header from binary data
+        reader.position += 1;
 
-        let result = self.create_data();
+        let mut image = Image::new(width, height);
+        for row in image.screen.iter_mut() {
+            for color in row.iter_mut() {
+                let mut channels = [0; 3];
+                for channel in channels.iter_mut() {
+                    let value = match format {
+                        PpmFormat::Ascii => reader.number()?,
+                        PpmFormat::Binary => reader.byte()? as usize,
+                    };
+                    if value > max_value {
+                        return Err(invalid_ppm("a channel is larger than the maximum value"));
+                    }
+                    *channel = (value * 255 / max_value) as u8;
+                }
+                *color = Color::new_color(channels[0], channels[1], channels[2]);
+            }
+        }
+        Ok(image)
+    }
 
-        match file.write_all(result.as_bytes()) {
-            Err(error) => panic!("failed to write image file because {}", error),
-            Ok(_) => {}
-        };
+    /// the colors of the image as 3 bytes per pixel, row by row from the top
+    pub fn rgb_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.width * self.height * 3);
+        for row in self.screen.iter() {
+            for color in row.iter() {
+                bytes.extend_from_slice(&[color.r, color.g, color.b]);
+            }
+        }
+        bytes
+    }
+
+    pub fn create_png(&self, file_name: &str) -> io::Result<()> {
+        let mut file = File::create(file_name)?;
+        let png = encode_png(self.width, self.height, &self.rgb_bytes());
+        file.write_all(&png)
+    }
+
+    /// saves the image in the format given by the extension of file_name.
+    /// png and ppm are written directly, and anything else is converted
+    /// from a ppm by ImageMagick
+    pub fn save(&self, file_name: &str) -> io::Result<()> {
+        let extension = Path::new(file_name)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        match extension.as_deref() {
+            Some("png") => self.create_png(file_name),
+            Some("ppm") => self.create_file(file_name, PpmFormat::Binary),
+            _ => self.convert_with_magick(file_name),
+        }
+    }
+
+    fn convert_with_magick(&self, file_name: &str) -> io::Result<()> {
+        let ppm_name = format!("{}.ppm", file_name);
+        self.create_file(&ppm_name, PpmFormat::Binary)?;
+        let status = Command::new("magick").arg("convert").arg(&ppm_name).arg(file_name).status();
+        fs::remove_file(&ppm_name)?;
+        let status = status.map_err(|error| match error.kind() {
+            io::ErrorKind::NotFound => io::Error::new(error.kind(), "saving formats other than png and ppm needs ImageMagick"),
+            _ => error,
+        })?;
+        if !status.success() {
+            return Err(io::Error::other(format!("magick convert exited with {}", status)));
+        }
+        Ok(())
     }
 
     pub fn clear(&mut self) {
         for i in 0..self.screen.len() {
             for v in 0..self.screen[0].len() {
                 self.screen[i][v].reset_color();
-                self.z_buffer[i][v] = f32::MIN;
+                self.z_buffer[i][v] = f32::NEG_INFINITY;
             }
         }
     }
 
-    pub fn display(&mut self) {
+    pub fn display(&mut self) -> io::Result<()> {
         let mut file_name: String = thread_rng()
             .sample_iter(&Alphanumeric)
             .take(30)
             .map(char::from)
             .collect();
         file_name = format!("/tmp/imageDisplay{}.ppm", file_name);
-        self.create_file(&file_name);
-        Command::new("open")
-            .arg(file_name)
-            .spawn()
-            .expect("failed to open image");
+        self.create_file(&file_name, PpmFormat::Binary)?;
+        Command::new("open").arg(file_name).spawn()?;
+        Ok(())
+    }
+}
+
+fn invalid_ppm(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid ppm file because {}", reason))
+}
+
+/// Reads the whitespace separated header and text channels of a ppm file,
+/// skipping # comments
+struct PpmReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> PpmReader<'a> {
+    fn token(&mut self) -> io::Result<&'a [u8]> {
+        loop {
+            match self.data.get(self.position) {
+                Some(byte) if byte.is_ascii_whitespace() => self.position += 1,
+                Some(b'#') => {
+                    while self.data.get(self.position).is_some_and(|&byte| byte != b'\n') {
+                        self.position += 1;
+                    }
+                }
+                Some(_) => break,
+                None => return Err(invalid_ppm("it ended early")),
+            }
+        }
+        let start = self.position;
+        while self.data.get(self.position).is_some_and(|byte| !byte.is_ascii_whitespace()) {
+            self.position += 1;
+        }
+        Ok(&self.data[start..self.position])
+    }
+
+    fn number(&mut self) -> io::Result<usize> {
+        let token = self.token()?;
+        std::str::from_utf8(token)
+            .ok()
+            .and_then(|token| token.parse().ok())
+            .ok_or_else(|| invalid_ppm("it has a value that is not a number"))
+    }
+
+    fn byte(&mut self) -> io::Result<u8> {
+        let byte = *self.data.get(self.position).ok_or_else(|| invalid_ppm("it ended early"))?;
+        self.position += 1;
+        Ok(byte)
     }
 }
 
-pub fn make_animation(name: String){
-    println!("Rendering gif...");
-    Command::new("convert")
-        .arg("-delay")
-        .arg("1.7")
-        .arg("animation/".to_owned()+&name+"*")
-        .arg(name+".gif")
-        .spawn()
-        .expect("ERROR: unable to convert a series of images to a gif");
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    /// writes image in the given format and reads it back
+    fn round_trip(image: &Image, format: PpmFormat) -> Image {
+        let file_name = env::temp_dir().join(format!("hw_11_round_trip_{}_{:?}.ppm", std::process::id(), format)).to_string_lossy().into_owned();
+        image.create_file(&file_name, format).unwrap();
+        let read = Image::read_ppm(&file_name);
+        fs::remove_file(&file_name).unwrap();
+        read.unwrap()
+    }
+
+    fn test_image() -> Image {
+        let mut image = Image::new(3, 2);
+        image.plot(0, 0, 0.0, &Color::new_color(255, 0, 0));
+        image.plot(2, 1, 0.0, &Color::new_color(1, 2, 3));
+        // a byte that is also whitespace must not be skipped in a P6 file
+        image.plot(1, 0, 0.0, &Color::new_color(b'\n', b' ', 0));
+        image
+    }
+
+    const NEAR: Color = Color::new_color(255, 0, 0);
+    const FAR: Color = Color::new_color(0, 0, 255);
+
+    /// plots a near and a far fragment on the same pixel, in the given order
+    fn winner(depth_test: DepthTest, near_first: bool) -> Color {
+        let mut image = Image::new(1, 1);
+        image.depth_test = depth_test;
+        let fragments = [(5.0, NEAR), (-5.0, FAR)];
+        let order = if near_first { [0, 1] } else { [1, 0] };
+        for i in order {
+            image.plot(0, 0, fragments[i].0, &fragments[i].1);
+        }
+        image.screen[0][0]
+    }
+
+    #[test]
+    fn depth_tests_pick_the_right_fragment() {
+        for near_first in [true, false] {
+            assert_eq!(winner(DepthTest::Greater, near_first), NEAR);
+            assert_eq!(winner(DepthTest::Less, near_first), FAR);
+        }
+        assert_eq!(winner(DepthTest::Always, true), FAR);
+        assert_eq!(winner(DepthTest::Always, false), NEAR);
+    }
+
+    #[test]
+    fn first_fragment_always_passes() {
+        for depth_test in [DepthTest::Greater, DepthTest::Less, DepthTest::Always] {
+            let mut image = Image::new(1, 1);
+            image.depth_test = depth_test;
+            image.plot(0, 0, -1e30, &NEAR);
+            assert_eq!(image.screen[0][0], NEAR, "{:?}", depth_test);
+        }
+    }
+
+    #[test]
+    fn depth_bias_pulls_coplanar_lines_in_front() {
+        // a line over a surface, whose depth comes out a little behind it
+        // because the two are interpolated differently
+        let (surface_z, line_z) = (0.3_f32, 0.3_f32 - 1e-5);
+        let mut image = Image::new(1, 1);
+        image.plot(0, 0, surface_z, &FAR);
+        image.plot(0, 0, line_z, &NEAR);
+        assert_eq!(image.screen[0][0], FAR);
+
+        let mut image = Image::new(1, 1);
+        image.plot(0, 0, surface_z, &FAR);
+        image.depth_bias = 1e-3;
+        image.plot(0, 0, line_z, &NEAR);
+        image.depth_bias = 0.0;
+        assert_eq!(image.screen[0][0], NEAR);
+        // and a surface drawn later at the same depth does not cover the line
+        image.plot(0, 0, surface_z, &FAR);
+        assert_eq!(image.screen[0][0], NEAR);
+    }
+
+    #[test]
+    fn other_formats_are_converted_without_leaving_the_ppm() {
+        let file_name = env::temp_dir().join(format!("hw_11_convert_{}.jpg", std::process::id())).to_string_lossy().into_owned();
+        // ImageMagick might not be installed, but either way the ppm it was given is removed
+        let saved = test_image().save(&file_name).is_ok();
+        assert!(!Path::new(&format!("{}.ppm", file_name)).exists());
+        assert_eq!(Path::new(&file_name).exists(), saved);
+        let _ = fs::remove_file(&file_name);
+    }
+
+    #[test]
+    fn read_ppm_round_trips_write_ppm() {
+        let image = test_image();
+        for format in [PpmFormat::Ascii, PpmFormat::Binary] {
+            let read = round_trip(&image, format);
+            assert_eq!((read.width, read.height), (image.width, image.height));
+            assert_eq!(read.screen, image.screen, "{:?}", format);
+        }
+    }
+}
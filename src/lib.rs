@@ -0,0 +1,59 @@
+//! Renders MDL scripts, and scenes built in Rust with [`Scene`], into
+//! images, ppm frames and animated gifs.
+
+pub mod antialias;
+pub mod ast;
+pub mod camera;
+pub mod clip;
+pub mod color;
+pub mod draw;
+pub mod easing;
+pub mod error;
+pub mod gif;
+pub mod gmath;
+pub mod image;
+pub mod lighting;
+pub mod matrix;
+mod mesh;
+pub mod parser;
+mod png;
+pub mod reflect;
+pub mod render;
+pub mod scene;
+
+pub use antialias::{Antialias, Filter};
+pub use ast::{Axis, Command, Statement};
+pub use color::Color;
+pub use error::{Diagnostic, MdlError};
+pub use image::Image;
+pub use matrix::Matrix;
+pub use parser::parse;
+pub use render::RenderOptions;
+pub use scene::Scene;
+
+extern crate pest;
+#[macro_use]
+extern crate pest_derive;
+
+pub mod consts {
+    use crate::color::Color;
+    use crate::reflect::ReflectionValue;
+
+    pub const AMBIENT_COLOR: Color = Color::new_color(50, 50, 50);
+    pub const AMBIENT_REFLECT: ReflectionValue = ReflectionValue::new_values(0.1, 0.1, 0.1);
+    pub const DIFFUSE_REFLECT: ReflectionValue = ReflectionValue::new_values(0.5, 0.5, 0.5);
+    pub const SPECULAR_REFLECT: ReflectionValue = ReflectionValue::new_values(0.5, 0.5, 0.5);
+    /// 1, so scripts that do not set a shininess light the same as before it existed
+    pub const SHININESS: f32 = 1.0;
+    pub const POINT_LIGHT_LOCATION: [f32; 3] = [0.5, 0.75, 1.0];
+    pub const POINT_LIGHT_COLOR: Color = Color::new_color(255, 255, 255);
+    pub const VIEW: [f32; 3] = [0.0, 0.0, 1.0];
+    pub const NEAR_PLANE: f32 = 1.0;
+    /// far beyond anything in a scene laid out in screen units
+    pub const FAR_PLANE: f32 = 100000.0;
+    pub const WELD_EPSILON: f32 = 0.01;
+    pub const STEP_2D: i32 = 100;
+    pub const STEP_3D: i32 = 100;
+    /// the width and height of the screen when a script does not set its size
+    pub const SIZE: usize = 500;
+}
@@ -0,0 +1,179 @@
+use crate::color::Color;
+use crate::consts;
+use crate::reflect::ReflectionValue;
+use std::collections::HashMap;
+use std::fmt;
+
+/// The reflection constants of a surface, set through `constants`,
+/// along with the intrinsic color it glows with
+#[derive(Clone, Debug)]
+pub struct Constants {
+    pub ambient_reflect: ReflectionValue,
+    pub diffuse_reflect: ReflectionValue,
+    pub specular_reflect: ReflectionValue,
+    pub red: f32,
+    pub green: f32,
+    pub blue: f32,
+    /// the exponent of the specular highlight: the higher it is, the
+    /// smaller and sharper the highlight
+    pub shininess: f32,
+}
+
+impl Constants {
+    /// takes the values in the order they are written after constants
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        ambient_red: f32,
+        diffuse_red: f32,
+        specular_red: f32,
+        ambient_green: f32,
+        diffuse_green: f32,
+        specular_green: f32,
+        ambient_blue: f32,
+        diffuse_blue: f32,
+        specular_blue: f32,
+        red: f32,
+        green: f32,
+        blue: f32,
+    ) -> Constants {
+        Constants {
+            ambient_reflect: ReflectionValue::new_values(ambient_red, ambient_green, ambient_blue),
+            diffuse_reflect: ReflectionValue::new_values(diffuse_red, diffuse_green, diffuse_blue),
+            specular_reflect: ReflectionValue::new_values(
+                specular_red,
+                specular_green,
+                specular_blue,
+            ),
+            red,
+            green,
+            blue,
+            shininess: consts::SHININESS,
+        }
+    }
+
+    pub fn with_shininess(mut self, shininess: f32) -> Constants {
+        self.shininess = shininess;
+        self
+    }
+}
+
+impl Constants {
+    /// the color the surface gives off without any light shining on it
+    pub fn intrinsic_color(&self) -> Color {
+        Color::from_f32(self.red, self.green, self.blue)
+    }
+}
+
+impl Default for Constants {
+    /// the reflection constants used by shapes that do not name any
+    fn default() -> Constants {
+        Constants {
+            ambient_reflect: consts::AMBIENT_REFLECT,
+            diffuse_reflect: consts::DIFFUSE_REFLECT,
+            specular_reflect: consts::SPECULAR_REFLECT,
+            red: 0.0,
+            green: 0.0,
+            blue: 0.0,
+            shininess: consts::SHININESS,
+        }
+    }
+}
+
+/// How the specular highlight is worked out, set through `specular`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpecularModel {
+    /// compares the reflection of the light with the view vector, which is
+    /// the default
+    Phong,
+    /// compares the normal with the vector halfway between the light and
+    /// the view. Its highlights are wider for the same shininess, and do not
+    /// get cut off at grazing angles
+    BlinnPhong,
+}
+
+impl SpecularModel {
+    /// the specular model with the given name, as written after specular
+    pub fn from_name(name: &str) -> Option<SpecularModel> {
+        match name {
+            "phong" => Some(SpecularModel::Phong),
+            "blinn" => Some(SpecularModel::BlinnPhong),
+            _ => None,
+        }
+    }
+}
+
+/// A point light source: the color of the light and the vector
+/// pointing towards it
+#[derive(Clone, Debug)]
+pub struct Light{
+    pub color: Color,
+    pub location: Vec<f32>,
+}
+
+impl Light{
+    pub fn new(color: Color, location: Vec<f32>) -> Light{
+        Light{color, location}
+    }
+
+    pub fn new_values(r: f32, g: f32, b: f32, x: f32, y: f32, z: f32) -> Light{
+        Light{color: Color::from_f32(r, g, b), location: vec![x, y, z]}
+    }
+}
+
+impl fmt::Display for Light{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        write!(f, "{} {} {} {}", self.color, self.location[0], self.location[1], self.location[2])
+    }
+}
+
+/// The lighting state of a scene: the ambient light, the named point
+/// lights, the named reflection constants and the specular model.
+/// `ambient`, `light`, `constants` and `specular` write into it and
+/// `Image::draw_polygons` reads from it.
+#[derive(Clone, Debug)]
+pub struct Lighting{
+    pub ambient: Color,
+    pub lights: Vec<(String, Light)>,
+    pub constants: HashMap<String, Constants>,
+    pub specular_model: SpecularModel,
+}
+
+impl Default for Lighting {
+    fn default() -> Lighting {
+        Lighting::new()
+    }
+}
+
+impl Lighting{
+    pub fn new() -> Lighting{
+        Lighting{ambient: consts::AMBIENT_COLOR, lights: Vec::new(), constants: HashMap::new(), specular_model: SpecularModel::Phong}
+    }
+
+    pub fn set_ambient(&mut self, ambient: Color){
+        self.ambient = ambient;
+    }
+
+    /// adds a light, replacing any light that already has the same name
+    pub fn add_light(&mut self, name: &str, light: Light){
+        self.lights.retain(|(light_name, _)| light_name != name);
+        self.lights.push((name.to_owned(), light));
+    }
+
+    pub fn add_constants(&mut self, name: &str, constants: Constants){
+        self.constants.insert(name.to_owned(), constants);
+    }
+
+    pub fn get_constants(&self, name: &str) -> Option<&Constants>{
+        self.constants.get(name)
+    }
+
+    /// the lights of the scene, falling back to the default point light
+    /// when the scene does not define any
+    pub fn active_lights(&self) -> Vec<Light>{
+        if self.lights.is_empty(){
+            vec![Light::new(consts::POINT_LIGHT_COLOR, consts::POINT_LIGHT_LOCATION.to_vec())]
+        }else{
+            self.lights.iter().map(|(_, light)| light.clone()).collect()
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use std::fmt;
 use std::f32;
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct Matrix{
     pub(in crate) matrix_array: Vec<Vec<f32>>,
 }
@@ -51,7 +51,7 @@ impl Matrix{
                 }
             }
         }
-        return identity_matrix;
+        identity_matrix
     }
     
     pub fn print_matrix(&self){
@@ -59,7 +59,7 @@ impl Matrix{
     }
     
     pub fn make_translate(x: f32, y: f32, z: f32) -> Matrix{
-        return Matrix::make_translate_with_scale(x,y,z,1.0);
+        Matrix::make_translate_with_scale(x,y,z,1.0)
     }
 
     pub fn make_translate_with_scale(x: f32, y: f32, z: f32, scale: f32) -> Matrix{
@@ -67,7 +67,7 @@ impl Matrix{
         matrix.matrix_array[0][3] = x * scale;
         matrix.matrix_array[1][3] = y * scale;
         matrix.matrix_array[2][3] = z * scale;
-        return matrix;
+        matrix
     }
     
     pub fn make_scale_with_scale( x: f32, y: f32, z: f32, scale: f32) -> Matrix{
@@ -75,21 +75,21 @@ impl Matrix{
         matrix.matrix_array[0][0] = x * scale;
         matrix.matrix_array[1][1] = y * scale;
         matrix.matrix_array[2][2] = z * scale;
-        return matrix;
+        matrix
     }
 
     pub fn make_scale( x: f32, y: f32, z: f32) -> Matrix{
-        return Matrix::make_scale_with_scale(x, y, z, 1.0);
+        Matrix::make_scale_with_scale(x, y, z, 1.0)
     }
     
     pub fn make_rot_x(mut theta: f32 ) -> Matrix{
         let mut matrix = Matrix::identity();
         theta = theta.to_radians();
         matrix.matrix_array[1][1] = theta.cos();
-        matrix.matrix_array[1][2] = theta.sin() * -1.0;
-        matrix.matrix_array[2][1] = matrix.matrix_array[1][2] * -1.0;
+        matrix.matrix_array[1][2] = -theta.sin();
+        matrix.matrix_array[2][1] = -matrix.matrix_array[1][2];
         matrix.matrix_array[2][2] = matrix.matrix_array[1][1];
-        return matrix;
+        matrix
     }
     
     pub fn make_rot_y( mut theta: f32 ) -> Matrix{
@@ -97,21 +97,65 @@ impl Matrix{
         theta = theta.to_radians();
         matrix.matrix_array[0][0] = theta.cos();
         matrix.matrix_array[0][2] = theta.sin();
-        matrix.matrix_array[2][0] = matrix.matrix_array[0][2] * -1.0;
+        matrix.matrix_array[2][0] = -matrix.matrix_array[0][2];
         matrix.matrix_array[2][2] = matrix.matrix_array[0][0];
-        return matrix;
+        matrix
     }
     
     pub fn make_rot_z( mut theta: f32 ) -> Matrix{
         let mut matrix = Matrix::identity();
         theta = theta.to_radians();
         matrix.matrix_array[0][0] = theta.cos();
-        matrix.matrix_array[0][1] = theta.sin() * -1.0;
-        matrix.matrix_array[1][0] = matrix.matrix_array[0][1] * -1.0;
+        matrix.matrix_array[0][1] = -theta.sin();
+        matrix.matrix_array[1][0] = -matrix.matrix_array[0][1];
         matrix.matrix_array[1][1] = matrix.matrix_array[0][0];
-        return matrix;
+        matrix
     }
     
+    ///Returns: The view matrix for a camera at eye looking towards aim,
+    ///moving the camera to the origin looking down the negative z axis
+    ///with up pointing along positive y
+    pub fn make_look_at(eye: &[f32], aim: &[f32], up: &[f32]) -> Matrix{
+        let mut forward = vec![eye[0] - aim[0], eye[1] - aim[1], eye[2] - aim[2]];
+        crate::gmath::normalize(&mut forward);
+        let mut right = vec![
+            up[1] * forward[2] - up[2] * forward[1],
+            up[2] * forward[0] - up[0] * forward[2],
+            up[0] * forward[1] - up[1] * forward[0],
+        ];
+        if right.iter().all(|&value| value == 0.0){
+            // looking straight up or down, so any right vector will do
+            right = vec![1.0, 0.0, 0.0];
+        }
+        crate::gmath::normalize(&mut right);
+        let true_up = [
+            forward[1] * right[2] - forward[2] * right[1],
+            forward[2] * right[0] - forward[0] * right[2],
+            forward[0] * right[1] - forward[1] * right[0],
+        ];
+        let mut matrix = Matrix::identity();
+        matrix.matrix_array[0][..3].copy_from_slice(&right);
+        matrix.matrix_array[1][..3].copy_from_slice(&true_up);
+        matrix.matrix_array[2][..3].copy_from_slice(&forward);
+        for row in 0..3{
+            matrix.matrix_array[row][3] = -(0..3).map(|i| matrix.matrix_array[row][i] * eye[i]).sum::<f32>();
+        }
+        matrix
+    }
+
+    ///Returns: The perspective projection matrix for the given focal
+    ///length. x and y are scaled by the focal length and w becomes the
+    ///distance in front of the camera, so dividing by w finishes the
+    ///projection
+    pub fn make_perspective(focal: f32) -> Matrix{
+        let mut matrix = Matrix::new(4,4);
+        matrix.matrix_array[0][0] = focal;
+        matrix.matrix_array[1][1] = focal;
+        matrix.matrix_array[2][2] = 1.0;
+        matrix.matrix_array[3][2] = -1.0;
+        matrix
+    }
+
     ///Returns: The correct 4x4 matrix that can be used
     ///to generate the coefiecients for a bezier curve
     pub fn make_bezier() -> Matrix{
@@ -126,7 +170,7 @@ impl Matrix{
         matrix.matrix_array[2][0] = -3.0;
         matrix.matrix_array[2][1] = 3.0;
         matrix.matrix_array[3][0] = 1.0;
-        return matrix;
+        matrix
     }
     ///Returns: The correct 4x4 matrix that can be used
     ///to generate the coefiecients for a hermite curve
@@ -142,7 +186,7 @@ impl Matrix{
         matrix.matrix_array[1][3] = -1.0;
         matrix.matrix_array[2][2] = 1.0;
         matrix.matrix_array[3][0] = 1.0;
-        return matrix;
+        matrix
     }
     
     /// Inputs:   double p1
@@ -160,24 +204,22 @@ impl Matrix{
     /// A matrix containing the values for a, b, c and d of the
     /// equation at^3 + bt^2 + ct + d for the curve defined
     /// by p1, p2, p3 and p4.
-    
     pub fn generate_curve_coefs( p0: f32, p1: f32, p2: f32, p3: f32, t: &CurveType ) -> Matrix{
         let mut matrix = Matrix::new(4,1);
         matrix.matrix_array[0][0] = p0;
         matrix.matrix_array[1][0] = p1;
         matrix.matrix_array[2][0] = p2; // r0 if hermite
         matrix.matrix_array[3][0] = p3; // r1 if hermite
-        let curve_matrix: Matrix;
-        match t{
+        let curve_matrix = match t{
             CurveType::Bezier=>{
-                curve_matrix =  Matrix::make_bezier();
+                Matrix::make_bezier()
             }
             CurveType::Hermite=>{
-                curve_matrix = Matrix::make_hermite();
+                Matrix::make_hermite()
             }
-        }
+        };
         matrix.multiply_matrixes(&curve_matrix);
-        return matrix;
+        matrix
     }
 }
 
@@ -188,7 +230,7 @@ impl fmt::Display for Matrix{
             for v in 0..self.matrix_array[i].len(){
                 result.push_str(&(format!("{} ",self.matrix_array[i][v]).to_string()));
             }
-            result.push_str("\n");
+            result.push('\n');
         }
         write!(f, "{}", result)
     }
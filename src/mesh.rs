@@ -0,0 +1,133 @@
+use crate::matrix::Matrix;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+
+fn invalid_data(line_number: usize, message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", line_number, message))
+}
+
+/// turns an OBJ vertex reference (1-based, or negative to count back from
+/// the latest vertex) into an index into vertices
+fn vertex_index(reference: &str, vertex_count: usize, line_number: usize) -> io::Result<usize> {
+    // a reference looks like v, v/vt, v//vn or v/vt/vn and only v is needed
+    let index: i64 = reference
+        .split('/')
+        .next()
+        .unwrap_or("")
+        .parse()
+        .map_err(|_| invalid_data(line_number, &format!("{} is not a vertex index", reference)))?;
+    let resolved = if index < 0 { vertex_count as i64 + index } else { index - 1 };
+    if resolved < 0 || resolved >= vertex_count as i64 {
+        return Err(invalid_data(line_number, &format!("vertex {} does not exist", index)));
+    }
+    Ok(resolved as usize)
+}
+
+impl Matrix {
+    /// add_mesh()
+    ///
+    /// Inputs:   struct matrix * polygons
+    ///
+    /// file_name: path to a Wavefront OBJ file
+    ///
+    /// Returns: an error if the file can not be read or is malformed
+    ///
+    /// adds the triangles of every face in the OBJ file to the polygon
+    /// matrix. Only the v and f statements are used; faces with more than
+    /// three vertices are split into a fan of triangles around their first
+    /// vertex.
+    pub fn add_mesh(&mut self, file_name: &str) -> io::Result<()> {
+        let reader = BufReader::new(File::open(file_name)?);
+        let mut vertices: Vec<[f32; 3]> = Vec::new();
+        for (line_index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line_number = line_index + 1;
+            let mut tokens = line.split_whitespace();
+            match tokens.next() {
+                Some("v") => {
+                    let mut vertex = [0.0; 3];
+                    for value in vertex.iter_mut() {
+                        *value = tokens
+                            .next()
+                            .and_then(|token| token.parse().ok())
+                            .ok_or_else(|| invalid_data(line_number, "a vertex needs x, y and z"))?;
+                    }
+                    vertices.push(vertex);
+                }
+                Some("f") => {
+                    let face = tokens
+                        .map(|reference| vertex_index(reference, vertices.len(), line_number))
+                        .collect::<io::Result<Vec<usize>>>()?;
+                    if face.len() < 3 {
+                        return Err(invalid_data(line_number, "a face needs at least 3 vertices"));
+                    }
+                    for corner in 1..face.len() - 1 {
+                        let (v0, v1, v2) = (vertices[face[0]], vertices[face[corner]], vertices[face[corner + 1]]);
+                        self.add_polygon(v0[0], v0[1], v0[2], v1[0], v1[1], v1[2], v2[0], v2[1], v2[2]);
+                    }
+                }
+                // normals, texture coordinates, groups and materials are not used
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    /// loads an OBJ file with the given contents into a new polygon matrix
+    fn load(name: &str, contents: &str) -> io::Result<Matrix> {
+        let file_name = env::temp_dir().join(format!("hw_11_{}_{}.obj", name, std::process::id())).to_string_lossy().into_owned();
+        fs::write(&file_name, contents).unwrap();
+        let mut polygons = Matrix::new(0, 0);
+        let result = polygons.add_mesh(&file_name);
+        fs::remove_file(&file_name).unwrap();
+        result.map(|_| polygons)
+    }
+
+    /// the x, y and z of each point of the polygon matrix
+    fn points(polygons: &Matrix) -> Vec<[f32; 3]> {
+        (0..polygons.matrix_array[0].len()).map(|i| [0, 1, 2].map(|axis| polygons.matrix_array[axis][i])).collect()
+    }
+
+    #[test]
+    fn quad_is_split_into_a_fan() {
+        let polygons = load("quad", "# a square\nv 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvn 0 0 1\nf 1/1/1 2//1 3 4\n").unwrap();
+        assert_eq!(
+            points(&polygons),
+            vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]]
+        );
+    }
+
+    #[test]
+    fn negative_references_count_back_from_the_latest_vertex() {
+        let polygons = load("negative", "v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\n").unwrap();
+        assert_eq!(points(&polygons), vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]);
+    }
+
+    #[test]
+    fn malformed_files_are_reported_with_their_line() {
+        for (name, contents, message) in [
+            ("missing_vertex", "v 0 0 0\nv 1 0 0\nf 1 2 3\n", "line 3: vertex 3 does not exist"),
+            ("short_vertex", "v 0 0\n", "line 1: a vertex needs x, y and z"),
+            ("short_face", "v 0 0 0\nv 1 0 0\nf 1 2\n", "line 3: a face needs at least 3 vertices"),
+            ("bad_reference", "v 0 0 0\nf 1 a 1\n", "line 2: a is not a vertex index"),
+            ("zero_reference", "v 0 0 0\nf 0 1 1\n", "line 2: vertex 0 does not exist"),
+        ] {
+            let error = load(name, contents).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+            assert_eq!(error.to_string(), message);
+        }
+    }
+
+    #[test]
+    fn missing_file_is_an_error() {
+        let mut polygons = Matrix::new(0, 0);
+        assert!(polygons.add_mesh("no such mesh.obj").is_err());
+    }
+}
@@ -1,12 +1,14 @@
-use std::process::Command;
-use crate::color::Color;
-use crate::consts;
-use crate::image::{Image, make_animation};
-use crate::matrix::CurveType;
-use crate::matrix::Matrix;
+use crate::antialias::{Antialias, Filter};
+use crate::ast::{Axis, Command, Statement};
+use crate::draw::ShadingType;
+use crate::easing::Easing;
+use crate::error::{Diagnostic, Diagnostics, MdlError, Span};
+use crate::image::DepthTest;
+use crate::lighting::{Constants, SpecularModel};
 use crate::pest::Parser;
-use crate::ReflectionValue;
-use std::collections::HashMap;
+use crate::render::{self, RenderOptions};
+use pest::iterators::Pair;
+use std::borrow::Cow;
 use std::fs::File;
 use std::io::{BufReader, Read};
 
@@ -14,438 +16,466 @@ use std::io::{BufReader, Read};
 #[grammar = "mdl.pest"]
 struct MDLParser;
 
-#[derive(Debug)]
-struct Constants {
-    pub ambient_reflect: ReflectionValue,
-    pub diffuse_reflect: ReflectionValue,
-    pub specular_reflect: ReflectionValue,
-    pub red: f32,
-    pub green: f32,
-    pub blue: f32,
+/// Renders the script in fname.
+/// Returns: the warnings about the script, or every diagnostic found in
+/// it if it could not be rendered
+pub fn parse(fname: &str, options: &RenderOptions) -> Result<Vec<Diagnostic>, MdlError> {
+    let mut diagnostics = Diagnostics::new();
+    match read_script(fname, &mut diagnostics) {
+        Some(statements) => render::render(fname, &statements, options, diagnostics),
+        None => Err(MdlError::new(fname, diagnostics)),
+    }
 }
 
-impl Constants {
-    fn new(
-        ambient_red: f32,
-        diffuse_red: f32,
-        specular_red: f32,
-        ambient_green: f32,
-        diffuse_green: f32,
-        specular_green: f32,
-        ambient_blue: f32,
-        diffuse_blue: f32,
-        specular_blue: f32,
-        red: f32,
-        green: f32,
-        blue: f32,
-    ) -> Constants {
-        Constants {
-            ambient_reflect: ReflectionValue::new_values(ambient_red, ambient_green, ambient_blue),
-            diffuse_reflect: ReflectionValue::new_values(diffuse_red, diffuse_green, diffuse_blue),
-            specular_reflect: ReflectionValue::new_values(
-                specular_red,
-                specular_green,
-                specular_blue,
-            ),
-            red,
-            green,
-            blue
+/// Reads the script in fname into statements.
+/// Returns: None if the file could not be read or does not match the grammar
+pub fn read_script(fname: &str, diagnostics: &mut Diagnostics) -> Option<Vec<Statement>> {
+    let mut instructions = String::new();
+    if let Err(error) = File::open(fname).and_then(|file| BufReader::new(file).read_to_string(&mut instructions)) {
+        diagnostics.general_error(format!("unable to read {} because {}", fname, error));
+        return None;
+    }
+    parse_statements(&instructions, diagnostics)
+}
+
+/// Turns the text of a script into statements. A command with a value that
+/// makes no sense is reported and left out, so the rest can still be checked.
+/// The commands of a script that does not match the grammar are checked too
+/// Returns: None if the script does not match the grammar
+pub fn parse_statements(instructions: &str, diagnostics: &mut Diagnostics) -> Option<Vec<Statement>> {
+    let script = parse_script(instructions, diagnostics)?;
+    let commands = MDLParser::parse(Rule::IDENT_LIST, &script).ok()?;
+    let mut statements = Vec::new();
+    for pair in commands {
+        if pair.as_rule() == Rule::EOI {
+            continue;
+        }
+        let span = Span::of(&pair);
+        let source = pair.as_str().to_owned();
+        if let Some(command) = command(pair, diagnostics) {
+            statements.push(Statement {
+                command,
+                span: Some(span),
+                source,
+            });
         }
     }
+    match script {
+        Cow::Borrowed(_) => Some(statements),
+        Cow::Owned(_) => None,
+    }
 }
 
-pub fn parse(fname: &str) {
-    let file = File::open(&fname).expect("Unable to open file");
-    let mut reader = BufReader::new(file);
-    let mut instructions = String::new();
-    reader.read_to_string(&mut instructions).expect("Unable to read file");
-    
-    let commands = MDLParser::parse(Rule::IDENT_LIST, &instructions);
-    let mut screen = Image::new(500, 500);
-    let color = Color::new_color(0, 255, 0);
-    let mut edges = Matrix::new(0, 0);
-    let mut polygons = Matrix::new(0, 0);
-    let mut cstack = vec![Matrix::new(0, 0); 0];
-    let mut constants_store = HashMap::new();
-    let mut basename = String::from("output");
-    let mut vary_exists = false;
-    let mut frames_exists = false;
-    let mut frames: Vec<HashMap<&str, f32>> = vec![HashMap::new()];
+/// Checks the script against the grammar, reporting every line that does
+/// not match it rather than stopping at the first one
+/// Returns: the script, with those lines commented out if there were any,
+/// or None if it still does not match
+fn parse_script<'a>(instructions: &'a str, diagnostics: &mut Diagnostics) -> Option<Cow<'a, str>> {
+    let mut error = match MDLParser::parse(Rule::IDENT_LIST, instructions) {
+        Ok(_) => return Some(Cow::Borrowed(instructions)),
+        Err(error) => error,
+    };
+    // each bad line is commented out, keeping the line numbers of the rest
+    let mut lines: Vec<String> = instructions.lines().map(|line| line.to_owned()).collect();
+    loop {
+        diagnostics.add(Diagnostic::from_pest(&error, instructions));
+        let line = match error.line_col {
+            pest::error::LineColLocation::Pos((line, _)) | pest::error::LineColLocation::Span((line, _), _) => line - 1,
+        };
+        if line >= lines.len() || lines[line].starts_with("//") {
+            return None;
+        }
+        lines[line] = String::from("//") + &lines[line];
+        let script = lines.join("\n") + "\n";
+        error = match MDLParser::parse(Rule::IDENT_LIST, &script) {
+            Ok(_) => return Some(Cow::Owned(script)),
+            Err(error) => error,
+        };
+    }
+}
 
-    clean_animation_directory();
-    cstack.push(Matrix::identity());
-    // to get the frame rate
-    for pair in commands.clone() {
-        for command in pair {
-            let error_message = command.as_str();
-            match command.as_rule(){
-                Rule::FRAMES_D => {
-                    let mut command_contents = command.into_inner();
-                    frames = vec![HashMap::new(); command_contents.next().unwrap().as_str().parse().expect(&*format!("Not a valid frame count at {}", error_message))];
-                    frames_exists = true;
-                }
-                Rule::BASENAME_S => {
-                    let mut command_contents = command.into_inner();
-                    basename = command_contents.nth(1).unwrap().as_str().to_owned();
-                }
-                Rule::BASENAME => {
-                    println!("WARNING: a default basename will be used instead because basename is missing at {}", error_message);
-                }
-                Rule::VARY_SDDDD => {
-                    vary_exists = true;
-                }
+/// The arguments of a command
+struct Arguments<'a> {
+    numbers: Vec<Pair<'a, Rule>>,
+    /// every name, along with how many numbers came before it
+    names: Vec<(usize, &'a str)>,
+}
+
+impl<'a> Arguments<'a> {
+    /// sorts the arguments of a command into its numbers and names
+    fn of(command: Pair<'a, Rule>) -> Arguments<'a> {
+        let mut arguments = Arguments {
+            numbers: Vec::new(),
+            names: Vec::new(),
+        };
+        for argument in command.into_inner() {
+            match argument.as_rule() {
+                Rule::DOUBLE => arguments.numbers.push(argument),
+                Rule::STRING | Rule::SHADING_TYPE => arguments.names.push((arguments.numbers.len(), argument.as_str())),
                 _ => {}
             }
         }
+        arguments
+    }
+
+    fn values(&self) -> Vec<f32> {
+        self.numbers.iter().map(double).collect()
     }
-    // pass 1
-    if vary_exists{
-        if !frames_exists{
-            println!("ERROR: vary used without frame numbers included");
-            return;
-        }else{
-            for pair in commands.clone() {
-                for command in pair {
-                    let error_message = command.as_str();
-                    match command.as_rule() {
-                        Rule::VARY_SDDDD => {
-                            let mut command_contents = command.into_inner();
-                            let knob_name = command_contents.next().unwrap().as_str();
-                            let start_frame: u32 = command_contents.next().unwrap().as_str().parse().expect(&*format!("Not a valid start frame number at {}", error_message));
-                            let end_frame: u32 = command_contents.next().unwrap().as_str().parse().expect(&*format!("Not a valid end frame number at {}", error_message));
-                            if end_frame < start_frame {
-                                println!("ERROR: start frame number is greater than end frame number at {}", error_message);
-                                return;
-                            }
-                            let start_value: f32 = command_contents.next().unwrap().as_str().parse().expect(&*format!("Not a valid start knob value at {}", error_message));
-                            let end_value: f32 = command_contents.next().unwrap().as_str().parse().expect(&*format!("Not a valid end knob value at {}", error_message));
-                            let frame_count = end_frame - start_frame;
-                            let mut current_value = start_value;
-                            let change_in_value = (end_value - start_value) / frame_count as f32;
-                            for frame_num in start_frame..=end_frame{
-                                frames[frame_num as usize].insert(knob_name, current_value);
-                                current_value += change_in_value;
-                            }
+
+    /// the nth name that comes after the given number of numbers
+    fn name(&self, numbers_before: usize, n: usize) -> Option<String> {
+        self.names.iter().filter(|(before, _)| *before == numbers_before).nth(n).map(|(_, name)| (*name).to_owned())
+    }
+
+    fn three(&self, start: usize) -> [f32; 3] {
+        let values = self.values();
+        [values[start], values[start + 1], values[start + 2]]
+    }
+}
+
+/// Turns one command of the script into a Command.
+/// Returns: None if the command was left out
+fn command(pair: Pair<Rule>, diagnostics: &mut Diagnostics) -> Option<Command> {
+    let at = pair.clone();
+    let rule = pair.as_rule();
+    let arguments = Arguments::of(pair);
+    let values = arguments.values();
+    let command = match rule {
+        Rule::FRAMES_D => Command::Frames { count: whole_number(&arguments.numbers[0], "frame count", &at, diagnostics)? },
+        Rule::BASENAME_S => Command::Basename { name: arguments.name(0, 0)? },
+        Rule::BASENAME => {
+            diagnostics.warning(&at, String::from("a default basename will be used instead because basename is missing"));
+            return None;
+        }
+        Rule::SIZE_DD => Command::Size { width: values[0] as usize, height: values[1] as usize },
+        Rule::DELAY_D => Command::Delay { delay: values[0].round().clamp(0.0, u16::MAX as f32) as u16 },
+        Rule::LOOP_D => Command::Loop { count: values[0].round().clamp(0.0, u16::MAX as f32) as u16 },
+        Rule::DITHER => Command::Dither,
+        Rule::ANTIALIAS_D | Rule::ANTIALIAS_DS => {
+            let factor = whole_number(&arguments.numbers[0], "antialias factor", &at, diagnostics)?;
+            let filter = match arguments.name(1, 0) {
+                Some(name) => Filter::from_name(&name).unwrap_or_else(|| {
+                    diagnostics.warning(&at, format!("{} is not a filter, the box filter will be used instead", name));
+                    Filter::Box
+                }),
+                None => Filter::Box,
+            };
+            Command::Antialias { antialias: Antialias::new(factor, filter) }
+        }
+        Rule::SET_SD => Command::Set { knob: arguments.name(0, 0)?, value: values[0] },
+        Rule::SETKNOBS_D => Command::SetKnobs { value: values[0] },
+        Rule::SAVE_KNOBS_S => Command::SaveKnobs { name: arguments.name(0, 0)? },
+        Rule::VARY_SDDDD | Rule::VARY_SDDDDS | Rule::VARY_SDDDDSDD => {
+            let (start_frame, end_frame) = frame_range(&arguments.numbers[0], &arguments.numbers[1], &at, diagnostics)?;
+            let easing = match arguments.name(4, 0) {
+                Some(easing_name) => {
+                    let controls = &values[4..];
+                    match (easing_name.as_str(), controls.len()) {
+                        ("bezier", 2) => Easing::Bezier(controls[0], controls[1]),
+                        ("bezier", _) => {
+                            diagnostics.warning(&at, String::from("bezier easing needs two control values, linear easing will be used instead"));
+                            Easing::Linear
                         }
-                        _ => {}
+                        (name, _) => Easing::from_name(name).unwrap_or_else(|| {
+                            diagnostics.warning(&at, format!("{} is not an easing, linear easing will be used instead", name));
+                            Easing::Linear
+                        }),
                     }
                 }
+                None => Easing::Linear,
+            };
+            Command::Vary {
+                knob: arguments.name(0, 0)?,
+                start_frame,
+                end_frame,
+                start_value: values[2],
+                end_value: values[3],
+                easing,
             }
         }
-    }
-    // pass 2
-    for frame_num in 0..frames.len(){
-        for pair in commands.clone() {
-            for command in pair {
-                let error_message = command.as_str();
-                match command.as_rule() {
-                    Rule::CONSTANTS_SDDDDDDDDD => {
-                        let mut command_contents = command.into_inner();
-                        let name = command_contents.next().unwrap().as_str();
-                        let constant = Constants::new(command_contents.next().unwrap().as_str().parse().expect(error_message), command_contents.next().unwrap().as_str().parse().expect(error_message), command_contents.next().unwrap().as_str().parse().expect(error_message), command_contents.next().unwrap().as_str().parse().expect(error_message), command_contents.next().unwrap().as_str().parse().expect(error_message), command_contents.next().unwrap().as_str().parse().expect(error_message), command_contents.next().unwrap().as_str().parse().expect(error_message), command_contents.next().unwrap().as_str().parse().expect(error_message), command_contents.next().unwrap().as_str().parse().expect(error_message), 0.0, 0.0, 0.0);
-                        constants_store.insert(name, constant);
-                    }
-                    // Rule::CONSTANTS_SDDDDDDDDDDDD => {
-                    //     let mut command_contents = command.into_inner();
-                    //     let name = command_contents.next().unwrap().as_str();
-                    //     let constant = Constants::new(command_contents.next().unwrap().as_str().parse().expect(error_message), command_contents.next().unwrap().as_str().parse().expect(error_message), command_contents.next().unwrap().as_str().parse().expect(error_message), command_contents.next().unwrap().as_str().parse().expect(error_message), command_contents.next().unwrap().as_str().parse().expect(error_message), command_contents.next().unwrap().as_str().parse().expect(error_message), command_contents.next().unwrap().as_str().parse().expect(error_message), command_contents.next().unwrap().as_str().parse().expect(error_message), command_contents.next().unwrap().as_str().parse().expect(error_message), command_contents.next().unwrap().as_str().parse().expect(error_message), command_contents.next().unwrap().as_str().parse().expect(error_message), command_contents.next().unwrap().as_str().parse().expect(error_message));
-                    //     constants_store.insert(name, constant);
-                    // }
-                    Rule::PPUSH => {
-                        cstack.push(cstack.last().unwrap().clone());
-                    }
-                    Rule::PPOP => {
-                        cstack.pop();
-                    }
-                    Rule::MOVE_DDD | Rule::MOVE_DDDS => {
-                        let mut command_contents = command.into_inner();
-                        let mut translate = Matrix::make_translate_with_scale(
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            if let Some(knob_name) = command_contents.next(){
-                                if frames[frame_num].contains_key(knob_name.as_str()){
-                                    *frames[frame_num].get(&*knob_name.as_str()).expect(error_message)
-                                }else{
-                                    0.0
-                                }
-                            }else{
-                                1.0
-                            }
-                        );
-                        translate.multiply_matrixes(&cstack.pop().unwrap());
-                        cstack.push(translate);
-                    }
-                    Rule::ROTATE_SD | Rule::ROTATE_SDS => {
-                        let mut command_contents = command.into_inner();
-                        let rot_axis = command_contents.next().unwrap().as_str();
-                        let mut rot_amount: f32 = command_contents.next().unwrap().as_str().parse().expect(error_message);
-                        if let Some(knob_name) = command_contents.next(){
-                            rot_amount *= if frames[frame_num].contains_key(knob_name.as_str()){
-                                *frames[frame_num].get(knob_name.as_str()).expect(error_message)
-                            }else {
-                                0.0
-                            }
-                        }
-                        match rot_axis {
-                            "x" => {
-                                let mut rot = Matrix::make_rot_x(rot_amount);
-                                rot.multiply_matrixes(&cstack.pop().unwrap());
-                                cstack.push(rot);
-                            }
-                            "y" => {
-                                let mut rot = Matrix::make_rot_y(rot_amount);
-                                rot.multiply_matrixes(&cstack.pop().unwrap());
-                                cstack.push(rot);
-                            }
-                            "z" => {
-                                let mut rot = Matrix::make_rot_z(rot_amount);
-                                rot.multiply_matrixes(&cstack.pop().unwrap());
-                                cstack.push(rot);
-                            }
-                            _ => {
-                                panic!(
-                                    "ERROR: Invalid input {} at {} for rotation: please use x, y, or z.",
-                                    rot_axis, error_message
-                                );
-                            }
-                        }
-                    }
-                    Rule::SCALE_DDD | Rule::SCALE_DDDS => {
-                        let mut command_contents = command.into_inner();
-                        let mut scale = Matrix::make_scale_with_scale(
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            if let Some(knob_name) = command_contents.next(){
-                                if frames[frame_num].contains_key(knob_name.as_str()){
-                                    *frames[frame_num].get(&*knob_name.as_str()).expect(error_message)
-                                }else{
-                                    0.0
-                                }
-                            }else{
-                                1.0
-                            }
-                        );
-                        scale.multiply_matrixes(&cstack.pop().unwrap());
-                        cstack.push(scale);
-                    }
-                    Rule::SPHERE_SDDDD => {
-                        // println!("{:?}", command);
-                        let mut command_contents = command.into_inner();
-                        let lighting_constants = constants_store.get(command_contents.next().unwrap().as_str()).expect("Unable to get lighting constants");
-                        polygons.add_sphere(
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            consts::STEP_3D,
-                        );
-                        polygons.multiply_matrixes(cstack.last().unwrap());
-                        screen.draw_polygons(
-                            &polygons,
-                            &color,
-                            &mut consts::VIEW.to_vec(),
-                            &consts::AMBIENT_COLOR,
-                            &mut consts::POINT_LIGHT_LOCATION.to_vec(),
-                            &consts::POINT_LIGHT_COLOR,
-                            &lighting_constants.ambient_reflect,
-                            &lighting_constants.diffuse_reflect,
-                            &lighting_constants.specular_reflect
-                        );
-    
-                        polygons = Matrix::new(0, 0);
-                    }
-                    Rule::SPHERE_DDDD => {
-                        let mut command_contents = command.into_inner();
-                        polygons.add_sphere(
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            consts::STEP_3D,
-                        );
-                        polygons.multiply_matrixes(cstack.last().unwrap());
-                        screen.draw_polygons(
-                            &polygons,
-                            &color,
-                            &mut consts::VIEW.to_vec(),
-                            &consts::AMBIENT_COLOR,
-                            &mut consts::POINT_LIGHT_LOCATION.to_vec(),
-                            &consts::POINT_LIGHT_COLOR,
-                            &consts::AMBIENT_REFLECT,
-                            &consts::DIFFUSE_REFLECT,
-                            &consts::SPECULAR_REFLECT
-                        );
-    
-                        polygons = Matrix::new(0, 0);
-                    }
-                    Rule::BOX_SDDDDDD => {
-                        let mut command_contents = command.into_inner();
-                        let lighting_constants = constants_store.get(command_contents.next().unwrap().as_str()).expect("Unable to get lighting constants");
-                        polygons.add_box(
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message)
-                        );
-                        polygons.multiply_matrixes(cstack.last().unwrap());
-    
-                        screen.draw_polygons(
-                            &polygons,
-                            &color,
-                            &mut consts::VIEW.to_vec(),
-                            &consts::AMBIENT_COLOR,
-                            &mut consts::POINT_LIGHT_LOCATION.to_vec(),
-                            &consts::POINT_LIGHT_COLOR,
-                            &lighting_constants.ambient_reflect,
-                            &lighting_constants.diffuse_reflect,
-                            &lighting_constants.specular_reflect
-                        );
-    
-                        polygons = Matrix::new(0, 0);
-                    }
-                    Rule::BOX_DDDDDD => {
-                        let mut command_contents = command.into_inner();
-                        polygons.add_box(
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message)
-                        );
-                        polygons.multiply_matrixes(cstack.last().unwrap());
-                        screen.draw_polygons(
-                            &polygons,
-                            &color,
-                            &mut consts::VIEW.to_vec(),
-                            &consts::AMBIENT_COLOR,
-                            &mut consts::POINT_LIGHT_LOCATION.to_vec(),
-                            &consts::POINT_LIGHT_COLOR,
-                            &consts::AMBIENT_REFLECT,
-                            &consts::DIFFUSE_REFLECT,
-                            &consts::SPECULAR_REFLECT
-                        );
-    
-                        polygons = Matrix::new(0, 0);
-                    }
-                    Rule::TORUS_SDDDDD => {
-                        let mut command_contents = command.into_inner();
-                        let lighting_constants = constants_store.get(command_contents.next().unwrap().as_str()).expect("Unable to get lighting constants");
-                        polygons.add_torus(
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            consts::STEP_3D
-                        );
-                        polygons.multiply_matrixes(cstack.last().unwrap());
-                        screen.draw_polygons(
-                            &polygons,
-                            &color,
-                            &mut consts::VIEW.to_vec(),
-                            &consts::AMBIENT_COLOR,
-                            &mut consts::POINT_LIGHT_LOCATION.to_vec(),
-                            &consts::POINT_LIGHT_COLOR,
-                            &lighting_constants.ambient_reflect,
-                            &lighting_constants.diffuse_reflect,
-                            &lighting_constants.specular_reflect
-                        );
-    
-                        polygons = Matrix::new(0, 0);
-                    }
-                    Rule::TORUS_DDDDD => {
-                        let mut command_contents = command.into_inner();
-                        polygons.add_torus(
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            consts::STEP_3D
-                        );
-                        polygons.multiply_matrixes(cstack.last().unwrap());
-                        screen.draw_polygons(
-                            &polygons,
-                            &color,
-                            &mut consts::VIEW.to_vec(),
-                            &consts::AMBIENT_COLOR,
-                            &mut consts::POINT_LIGHT_LOCATION.to_vec(),
-                            &consts::POINT_LIGHT_COLOR,
-                            &consts::AMBIENT_REFLECT,
-                            &consts::DIFFUSE_REFLECT,
-                            &consts::SPECULAR_REFLECT
-                        );
-    
-                        polygons = Matrix::new(0, 0);
-                    }
-                    Rule::DISPLAY => {
-                        if frames.len() <= 1{
-                            screen.display();
-                        }
-                    }
-                    Rule::SAVE_S => {
-                        if frames.len() <= 1{
-                            let mut command_contents = command.into_inner();
-                            let filename = command_contents.next().unwrap().as_str();
-                            screen.create_file(filename);
-                            Command::new("magick")
-                                .arg("convert")
-                                .arg(filename)
-                                .arg(filename)
-                                .spawn()
-                                .expect("failed to convert image to desired format");
-                        }
-                    }
-                    Rule::LINE_DDDDDD => {
-                        let mut command_contents = command.into_inner();
-                        edges.add_edge(
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                            command_contents.next().unwrap().as_str().parse().expect(error_message),
-                        );
-                        edges.multiply_matrixes(cstack.last().unwrap());
-                        screen.draw_lines(&edges, &color);
-        
-                        edges = Matrix::new(0, 0);
-                    }
-                    Rule::EOI | Rule::VARY_SDDDD | Rule::BASENAME_S | Rule::BASENAME | Rule::FRAMES_D => {}
-                    _ => {
-                        println!("{:?} was not implemented :/", command.as_rule());
-                    }
+        Rule::TWEEN_DDSS => {
+            let (start_frame, end_frame) = frame_range(&arguments.numbers[0], &arguments.numbers[1], &at, diagnostics)?;
+            Command::Tween {
+                start_frame,
+                end_frame,
+                start_knobs: arguments.name(2, 0)?,
+                end_knobs: arguments.name(2, 1)?,
+            }
+        }
+        Rule::CAMERA_DDDDDD | Rule::CAMERA_DDDDDDS | Rule::CAMERA_DDDDDDSS => Command::Camera {
+            eye: arguments.three(0),
+            aim: arguments.three(3),
+            // the first knob scales the eye, the second scales the aim
+            eye_knob: arguments.name(6, 0),
+            aim_knob: arguments.name(6, 1),
+        },
+        Rule::FOCAL_D => Command::Focal { focal: values[0] },
+        Rule::CONSTANTS_SDDDDDDDDD | Rule::CONSTANTS_SDDDDDDDDDD | Rule::CONSTANTS_SDDDDDDDDDDDD | Rule::CONSTANTS_SDDDDDDDDDDDDD => {
+            // the 12 and 13 value forms go on with the color the surface gives
+            // off by itself, and the 10 and 13 value forms end with its shininess
+            let (red, green, blue) = if values.len() >= 12 { (values[9], values[10], values[11]) } else { (0.0, 0.0, 0.0) };
+            let mut constants = Constants::new(values[0], values[1], values[2], values[3], values[4], values[5], values[6], values[7], values[8], red, green, blue);
+            if values.len() == 10 || values.len() == 13 {
+                let shininess = values[values.len() - 1];
+                if shininess < 0.0 {
+                    diagnostics.error(&at, format!("shininess must not be negative, but it is {}", shininess));
+                    return None;
                 }
+                constants = constants.with_shininess(shininess);
             }
+            Command::Constants { name: arguments.name(0, 0)?, constants }
         }
-        if frames.len() > 1{
-            render_reset_image_canvas(&basename, frame_num, &mut screen, &mut edges, &mut polygons, &mut cstack);
+        Rule::LIGHT_SDDDDDD | Rule::LIGHT_SDDDDDDS | Rule::LIGHT_SDDDDDDSS => Command::Light {
+            name: arguments.name(0, 0)?,
+            color: arguments.three(0),
+            location: arguments.three(3),
+            // the first knob scales the color, the second scales the location
+            color_knob: arguments.name(6, 0),
+            location_knob: arguments.name(6, 1),
+        },
+        Rule::AMBIENT_DDD => Command::Ambient { color: arguments.three(0) },
+        Rule::SHADING_ST => Command::Shading {
+            shading: match arguments.name(0, 0)?.as_str() {
+                "flat" => ShadingType::Flat,
+                "gouraud" => ShadingType::Gouraud,
+                "phong" => ShadingType::Phong,
+                "wireframe" => ShadingType::Wireframe,
+                shading_type => {
+                    diagnostics.warning(&at, format!("{} shading is not supported, flat shading will be used instead", shading_type));
+                    ShadingType::Flat
+                }
+            },
+        },
+        Rule::DEPTH_TEST_S => {
+            let name = arguments.name(0, 0)?;
+            match DepthTest::from_name(&name) {
+                Some(test) => Command::DepthTest { test },
+                None => {
+                    diagnostics.error(&at, format!("{} is not a depth test, please use less, greater or always", name));
+                    return None;
+                }
+            }
+        }
+        Rule::DEPTH_BIAS_D => Command::DepthBias { bias: values[0] },
+        Rule::SPECULAR_S => {
+            let name = arguments.name(0, 0)?;
+            match SpecularModel::from_name(&name) {
+                Some(model) => Command::Specular { model },
+                None => {
+                    diagnostics.error(&at, format!("{} is not a specular model, please use phong or blinn", name));
+                    return None;
+                }
+            }
+        }
+        Rule::PPUSH => Command::Push,
+        Rule::PPOP => Command::Pop,
+        Rule::MOVE_DDD | Rule::MOVE_DDDS => Command::Move { offset: arguments.three(0), knob: arguments.name(3, 0) },
+        Rule::SCALE_DDD | Rule::SCALE_DDDS => Command::Scale { scale: arguments.three(0), knob: arguments.name(3, 0) },
+        Rule::ROTATE_SD | Rule::ROTATE_SDS => {
+            let axis = match arguments.name(0, 0)?.as_str() {
+                "x" => Axis::X,
+                "y" => Axis::Y,
+                "z" => Axis::Z,
+                axis => {
+                    diagnostics.error(&at, format!("{} is not an axis, please use x, y or z", axis));
+                    return None;
+                }
+            };
+            Command::Rotate { axis, degrees: values[0], knob: arguments.name(1, 0) }
+        }
+        Rule::SAVE_COORDS_S => Command::SaveCoordSystem { name: arguments.name(0, 0)? },
+        Rule::SPHERE_DDDD | Rule::SPHERE_DDDDS | Rule::SPHERE_SDDDD | Rule::SPHERE_SDDDDS => Command::Sphere {
+            constants: arguments.name(0, 0),
+            center: arguments.three(0),
+            radius: values[3],
+            coord_system: arguments.name(4, 0),
+        },
+        Rule::BOX_DDDDDD | Rule::BOX_DDDDDDS | Rule::BOX_SDDDDDD | Rule::BOX_SDDDDDDS => Command::Box {
+            constants: arguments.name(0, 0),
+            corner: arguments.three(0),
+            size: arguments.three(3),
+            coord_system: arguments.name(6, 0),
+        },
+        Rule::TORUS_DDDDD | Rule::TORUS_DDDDDS | Rule::TORUS_SDDDDD | Rule::TORUS_SDDDDDS => Command::Torus {
+            constants: arguments.name(0, 0),
+            center: arguments.three(0),
+            minor_radius: values[3],
+            major_radius: values[4],
+            coord_system: arguments.name(5, 0),
+        },
+        // a coordinate system after the first point applies to it,
+        // one after the second point applies to the second
+        Rule::LINE_DDDDDD | Rule::LINE_DDDSDDD | Rule::LINE_DDDDDDS | Rule::LINE_DDDSDDDS |
+        Rule::LINE_SDDDDDD | Rule::LINE_SDDDSDDD | Rule::LINE_SDDDDDDS | Rule::LINE_SDDDSDDDS => Command::Line {
+            constants: arguments.name(0, 0),
+            start: arguments.three(0),
+            start_coord_system: arguments.name(3, 0),
+            end: arguments.three(3),
+            end_coord_system: arguments.name(6, 0),
+        },
+        // the names of a mesh all come before any numbers, so they are told
+        // apart by how many there are
+        Rule::MESH_CS => Command::Mesh { constants: None, file_name: arguments.name(0, 0)?, coord_system: None },
+        Rule::MESH_SCS | Rule::MESH_SCSS => Command::Mesh {
+            constants: arguments.name(0, 0),
+            file_name: arguments.name(0, 1)?,
+            coord_system: arguments.name(0, 2),
+        },
+        Rule::SAVE_S => Command::Save { file_name: arguments.name(0, 0)? },
+        Rule::DISPLAY => Command::Display,
+        _ => {
+            diagnostics.warning(&at, format!("{:?} is not implemented, so it was skipped", rule));
+            return None;
+        }
+    };
+    Some(command)
+}
+
+/// the value of a DOUBLE, which the grammar only matches for valid numbers
+fn double(pair: &Pair<Rule>) -> f32 {
+    pair.as_str().parse().unwrap_or(0.0)
+}
+
+/// Returns: a DOUBLE that counts something, such as frames, if it is a whole number
+fn whole_number(pair: &Pair<Rule>, what: &str, at: &Pair<Rule>, diagnostics: &mut Diagnostics) -> Option<usize> {
+    match pair.as_str().parse() {
+        Ok(number) => Some(number),
+        Err(_) => {
+            diagnostics.error(at, format!("{} is not a valid {}", pair.as_str(), what));
+            None
         }
-    }
-    if frames.len() > 1{
-        make_animation(basename);
     }
 }
 
-fn render_reset_image_canvas(filename: &str, frame_num: usize, screen: &mut Image, edges: &mut Matrix, polygons: &mut Matrix, cstack: &mut Vec<Matrix>){
-    let filename = "animation/".to_owned() + &filename + &*format!("{:04}", frame_num) + ".ppm";
-    screen.create_file(&*filename);
-    println!("Rendering {}...", filename);
-    screen.clear();
-    *edges = Matrix::new(0, 0);
-    *polygons = Matrix::new(0, 0);
-    *cstack = vec![Matrix::new(0, 0); 0];
-    cstack.push(Matrix::identity());
+/// Returns: the first and last frame of a vary or tween, if they are in order.
+/// Whether they are frames of the animation is checked once every command is read
+fn frame_range(start: &Pair<Rule>, end: &Pair<Rule>, at: &Pair<Rule>, diagnostics: &mut Diagnostics) -> Option<(usize, usize)> {
+    let start_frame = whole_number(start, "start frame number", at, diagnostics);
+    let end_frame = whole_number(end, "end frame number", at, diagnostics);
+    Some((start_frame?, end_frame?))
 }
 
-fn clean_animation_directory(){
-    Command::new("make")
-        .arg("clean_anim")
-        .spawn()
-        .expect("ERROR: unable to delete files");
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    /// the commands of a script that has to parse without errors
+    fn commands(script: &str) -> Vec<Command> {
+        let mut diagnostics = Diagnostics::new();
+        let statements = parse_statements(script, &mut diagnostics).expect("the script should parse");
+        assert!(!diagnostics.has_errors(), "{:?}", diagnostics.list);
+        statements.into_iter().map(|statement| statement.command).collect()
+    }
+
+    #[test]
+    fn set_sets_one_knob() {
+        match commands("set spin 0.5\n").as_slice() {
+            [Command::Set { knob, value }] => {
+                assert_eq!(knob, "spin");
+                assert_eq!(*value, 0.5);
+            }
+            other => panic!("expected a set, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn setknobs_is_not_read_as_set() {
+        match commands("setknobs 5\n").as_slice() {
+            [Command::SetKnobs { value }] => assert_eq!(*value, 5.0),
+            other => panic!("expected a setknobs, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn set_needs_a_space_after_the_keyword() {
+        let mut diagnostics = Diagnostics::new();
+        assert!(parse_statements("setspin 0.5\n", &mut diagnostics).is_none());
+        assert!(diagnostics.has_errors());
+    }
+
+    #[test]
+    fn commands_are_checked_when_another_line_does_not_parse() {
+        let mut diagnostics = Diagnostics::new();
+        assert!(parse_statements("bogus line\nrotate q 30\n", &mut diagnostics).is_none());
+        let messages: Vec<&str> = diagnostics.list.iter().map(|diagnostic| diagnostic.message.as_str()).collect();
+        assert_eq!(messages.len(), 2, "{:?}", messages);
+        assert_eq!(messages[0], "this is not a valid command");
+        assert!(messages[1].contains("q is not an axis"), "{:?}", messages);
+        assert_eq!(diagnostics.list[1].span, Some(Span { line: 2, column: 1 }));
+    }
+
+    #[test]
+    fn shapes_read_their_constants_and_coordinate_system() {
+        match commands("save_coord_system saved\nsphere shiny 0 0 0 10 saved\nbox 0 0 0 1 1 1 saved\nmesh shiny :cube.obj saved\n").as_slice() {
+            [
+                Command::SaveCoordSystem { name },
+                Command::Sphere { constants: sphere_constants, coord_system: sphere_coord_system, .. },
+                Command::Box { constants: box_constants, coord_system: box_coord_system, .. },
+                Command::Mesh { constants: mesh_constants, file_name, coord_system: mesh_coord_system },
+            ] => {
+                assert_eq!(name, "saved");
+                assert_eq!((sphere_constants.as_deref(), sphere_coord_system.as_deref()), (Some("shiny"), Some("saved")));
+                assert_eq!((box_constants.as_deref(), box_coord_system.as_deref()), (None, Some("saved")));
+                assert_eq!(file_name, "cube.obj");
+                assert_eq!((mesh_constants.as_deref(), mesh_coord_system.as_deref()), (Some("shiny"), Some("saved")));
+            }
+            other => panic!("expected a save_coord_system and three shapes, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn line_coordinate_systems_apply_to_the_point_before_them() {
+        match commands("line 0 0 0 first 1 1 1\nline 0 0 0 1 1 1 second\n").as_slice() {
+            [
+                Command::Line { start_coord_system: first_start, end_coord_system: first_end, .. },
+                Command::Line { start_coord_system: second_start, end_coord_system: second_end, .. },
+            ] => {
+                assert_eq!((first_start.as_deref(), first_end.as_deref()), (Some("first"), None));
+                assert_eq!((second_start.as_deref(), second_end.as_deref()), (None, Some("second")));
+            }
+            other => panic!("expected two lines, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn twelve_value_constants_end_with_the_intrinsic_color() {
+        let script = "constants dull 0.1 0.2 0.3 0.1 0.2 0.3 0.1 0.2 0.3\nconstants glow 0.1 0.2 0.3 0.1 0.2 0.3 0.1 0.2 0.3 40 80 120\n";
+        match commands(script).as_slice() {
+            [Command::Constants { constants: dull, .. }, Command::Constants { name, constants: glow }] => {
+                assert_eq!(dull.intrinsic_color(), Color::new_color(0, 0, 0));
+                assert_eq!(name, "glow");
+                assert_eq!(glow.intrinsic_color(), Color::new_color(40, 80, 120));
+            }
+            other => panic!("expected two constants, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn size_reads_the_width_and_height() {
+        match commands("size 300 200\n").as_slice() {
+            [Command::Size { width, height }] => assert_eq!((*width, *height), (300, 200)),
+            other => panic!("expected a size, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn tween_reads_its_frames_and_knob_lists() {
+        match commands("tween 0 10 start end\n").as_slice() {
+            [Command::Tween { start_frame, end_frame, start_knobs, end_knobs }] => {
+                assert_eq!((*start_frame, *end_frame), (0, 10));
+                assert_eq!(start_knobs, "start");
+                assert_eq!(end_knobs, "end");
+            }
+            other => panic!("expected a tween, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn tween_frames_must_be_whole_numbers() {
+        let mut diagnostics = Diagnostics::new();
+        let statements = parse_statements("tween 1.5 3 start end\n", &mut diagnostics).unwrap();
+        assert!(statements.is_empty());
+        assert!(diagnostics.has_errors());
+    }
+}
@@ -0,0 +1,468 @@
+//! A small PNG encoder for 8 bit RGB images.
+//!
+//! The image data is filtered row by row, compressed with deflate
+//! (LZ77 matches written with the fixed Huffman codes) inside a zlib
+//! stream, and split into the IHDR, IDAT and IEND chunks.
+
+const SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+const BYTES_PER_PIXEL: usize = 3;
+
+// deflate limits
+const WINDOW_SIZE: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: usize = 15;
+const MAX_CHAIN: usize = 64;
+
+const LENGTH_BASES: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA_BITS: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASES: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA_BITS: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Returns: the bytes of a PNG file for an image of the given size,
+/// where rgb holds 3 bytes per pixel, row by row from the top
+pub fn encode_png(width: usize, height: usize, rgb: &[u8]) -> Vec<u8> {
+    let mut png = SIGNATURE.to_vec();
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // bit depth 8, color type 2 (rgb), deflate, adaptive filtering, no interlace
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+    write_chunk(&mut png, b"IHDR", &header);
+
+    let filtered = filter_rows(width, height, rgb);
+    write_chunk(&mut png, b"IDAT", &zlib_compress(&filtered));
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn write_chunk(png: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(chunk_type);
+    png.extend_from_slice(data);
+    let mut crc = Crc32::new();
+    crc.update(chunk_type);
+    crc.update(data);
+    png.extend_from_slice(&crc.finish().to_be_bytes());
+}
+
+/// CRC-32 as used by PNG chunks
+pub struct Crc32 {
+    table: [u32; 256],
+    crc: u32,
+}
+
+impl Crc32 {
+    pub fn new() -> Crc32 {
+        let mut table = [0; 256];
+        for (n, entry) in table.iter_mut().enumerate() {
+            let mut c = n as u32;
+            for _ in 0..8 {
+                c = if c & 1 == 1 { 0xedb88320 ^ (c >> 1) } else { c >> 1 };
+            }
+            *entry = c;
+        }
+        Crc32 { table, crc: 0xffffffff }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.crc = self.table[((self.crc ^ byte as u32) & 0xff) as usize] ^ (self.crc >> 8);
+        }
+    }
+
+    pub fn finish(&self) -> u32 {
+        self.crc ^ 0xffffffff
+    }
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let mut a: u32 = 1;
+    let mut b: u32 = 0;
+    // 5552 is the most bytes that can be summed before b could overflow
+    for block in data.chunks(5552) {
+        for &byte in block {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let p = left as i16 + up as i16 - up_left as i16;
+    let pa = (p - left as i16).abs();
+    let pb = (p - up as i16).abs();
+    let pc = (p - up_left as i16).abs();
+    if pa <= pb && pa <= pc {
+        left
+    } else if pb <= pc {
+        up
+    } else {
+        up_left
+    }
+}
+
+/// Runs each row through every PNG filter and keeps the one whose output
+/// has the smallest sum of absolute values (as signed bytes), which
+/// usually compresses best. Each row starts with its filter type.
+fn filter_rows(width: usize, height: usize, rgb: &[u8]) -> Vec<u8> {
+    let stride = width * BYTES_PER_PIXEL;
+    let mut filtered = Vec::with_capacity((stride + 1) * height);
+    let empty_row = vec![0; stride];
+    let mut candidate = vec![0; stride];
+    let mut best = vec![0; stride];
+    for y in 0..height {
+        let row = &rgb[y * stride..(y + 1) * stride];
+        let previous = if y > 0 { &rgb[(y - 1) * stride..y * stride] } else { &empty_row[..] };
+        let mut best_filter = 0;
+        let mut best_score = u64::MAX;
+        for filter in 0..5u8 {
+            for i in 0..stride {
+                let left = if i >= BYTES_PER_PIXEL { row[i - BYTES_PER_PIXEL] } else { 0 };
+                let up = previous[i];
+                let up_left = if i >= BYTES_PER_PIXEL { previous[i - BYTES_PER_PIXEL] } else { 0 };
+                let prediction = match filter {
+                    0 => 0,
+                    1 => left,
+                    2 => up,
+                    3 => ((left as u16 + up as u16) / 2) as u8,
+                    _ => paeth(left, up, up_left),
+                };
+                candidate[i] = row[i].wrapping_sub(prediction);
+            }
+            let score: u64 = candidate.iter().map(|&byte| (byte as i8).unsigned_abs() as u64).sum();
+            if score < best_score {
+                best_score = score;
+                best_filter = filter;
+                best.copy_from_slice(&candidate);
+            }
+        }
+        filtered.push(best_filter);
+        filtered.extend_from_slice(&best);
+    }
+    filtered
+}
+
+/// Writes bits into bytes, least significant bit first, as deflate expects
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn new() -> BitWriter {
+        BitWriter { bytes: Vec::new(), buffer: 0, count: 0 }
+    }
+
+    fn write_bits(&mut self, value: u32, count: u32) {
+        self.buffer |= (value as u64) << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Huffman codes are packed starting from their most significant bit
+    fn write_code(&mut self, code: u32, length: u32) {
+        let reversed = code.reverse_bits() >> (32 - length);
+        self.write_bits(reversed, length);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+/// writes a literal/length symbol with the fixed Huffman code
+fn write_fixed_literal(writer: &mut BitWriter, symbol: u32) {
+    match symbol {
+        0..=143 => writer.write_code(0x30 + symbol, 8),
+        144..=255 => writer.write_code(0x190 + symbol - 144, 9),
+        256..=279 => writer.write_code(symbol - 256, 7),
+        _ => writer.write_code(0xc0 + symbol - 280, 8),
+    }
+}
+
+fn write_match(writer: &mut BitWriter, length: usize, distance: usize) {
+    let length_code = LENGTH_BASES.iter().rposition(|&base| base as usize <= length).unwrap();
+    write_fixed_literal(writer, 257 + length_code as u32);
+    writer.write_bits(
+        (length - LENGTH_BASES[length_code] as usize) as u32,
+        LENGTH_EXTRA_BITS[length_code] as u32,
+    );
+    let distance_code = DISTANCE_BASES.iter().rposition(|&base| base as usize <= distance).unwrap();
+    writer.write_code(distance_code as u32, 5);
+    writer.write_bits(
+        (distance - DISTANCE_BASES[distance_code] as usize) as u32,
+        DISTANCE_EXTRA_BITS[distance_code] as u32,
+    );
+}
+
+fn hash(data: &[u8], i: usize) -> usize {
+    let value = (data[i] as usize) << 16 | (data[i + 1] as usize) << 8 | data[i + 2] as usize;
+    (value.wrapping_mul(2654435761) >> 8) & ((1 << HASH_BITS) - 1)
+}
+
+/// Returns: data compressed into a single fixed Huffman deflate block,
+/// using a hash chain to find earlier repeats of each position
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::new();
+    // final block, fixed Huffman codes
+    writer.write_bits(1, 1);
+    writer.write_bits(1, 2);
+
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut previous = vec![usize::MAX; WINDOW_SIZE];
+    let insert = |head: &mut Vec<usize>, previous: &mut Vec<usize>, i: usize| {
+        if i + MIN_MATCH <= data.len() {
+            let h = hash(data, i);
+            previous[i % WINDOW_SIZE] = head[h];
+            head[h] = i;
+        }
+    };
+
+    let mut i = 0;
+    while i < data.len() {
+        let mut best_length = 0;
+        let mut best_distance = 0;
+        if i + MIN_MATCH <= data.len() {
+            let mut candidate = head[hash(data, i)];
+            let mut chain = 0;
+            let max_length = MAX_MATCH.min(data.len() - i);
+            while candidate != usize::MAX && i - candidate < WINDOW_SIZE && chain < MAX_CHAIN {
+                let mut length = 0;
+                while length < max_length && data[candidate + length] == data[i + length] {
+                    length += 1;
+                }
+                if length > best_length {
+                    best_length = length;
+                    best_distance = i - candidate;
+                    if length == max_length {
+                        break;
+                    }
+                }
+                let next = previous[candidate % WINDOW_SIZE];
+                // entries older than the window have been overwritten
+                if next == usize::MAX || next >= candidate {
+                    break;
+                }
+                candidate = next;
+                chain += 1;
+            }
+        }
+        if best_length >= MIN_MATCH {
+            write_match(&mut writer, best_length, best_distance);
+            for position in i..i + best_length {
+                insert(&mut head, &mut previous, position);
+            }
+            i += best_length;
+        } else {
+            write_fixed_literal(&mut writer, data[i] as u32);
+            insert(&mut head, &mut previous, i);
+            i += 1;
+        }
+    }
+    write_fixed_literal(&mut writer, 256);
+    writer.finish()
+}
+
+/// Returns: data wrapped in a zlib stream
+pub fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    // deflate with a 32K window, and a check value that makes the header a multiple of 31
+    let mut stream = vec![0x78, 0x01];
+    stream.extend(deflate(data));
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn crc32(data: &[u8]) -> u32 {
+        let mut crc = Crc32::new();
+        crc.update(data);
+        crc.finish()
+    }
+
+    #[test]
+    fn crc32_known_values() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+        // the crc that ends every IEND chunk
+        assert_eq!(crc32(b"IEND"), 0xae426082);
+    }
+
+    #[test]
+    fn crc32_can_be_updated_in_pieces() {
+        let mut crc = Crc32::new();
+        crc.update(b"1234");
+        crc.update(b"56789");
+        assert_eq!(crc.finish(), 0xcbf43926);
+    }
+
+    #[test]
+    fn adler32_known_values() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e60398);
+        // long enough that the sums have to be reduced along the way
+        assert_eq!(adler32(&[0xff; 100000]), 0x149a302c);
+    }
+
+    /// Reads bits least significant first, the way BitWriter writes them
+    struct BitReader<'a> {
+        bytes: &'a [u8],
+        position: usize,
+    }
+
+    impl BitReader<'_> {
+        fn bits(&mut self, count: u32) -> u32 {
+            let mut value = 0;
+            for i in 0..count {
+                let bit = (self.bytes[self.position / 8] >> (self.position % 8)) & 1;
+                value |= (bit as u32) << i;
+                self.position += 1;
+            }
+            value
+        }
+
+        /// Huffman codes are read from their most significant bit
+        fn code(&mut self, length: u32) -> u32 {
+            (0..length).fold(0, |code, _| code << 1 | self.bits(1))
+        }
+
+        fn fixed_literal(&mut self) -> u32 {
+            let code = self.code(7);
+            if code <= 0x17 {
+                return 256 + code;
+            }
+            let code = code << 1 | self.bits(1);
+            match code {
+                0x30..=0xbf => code - 0x30,
+                0xc0..=0xc7 => 280 + code - 0xc0,
+                _ => 144 + (code << 1 | self.bits(1)) - 0x190,
+            }
+        }
+    }
+
+    /// a deflate decoder for the fixed Huffman blocks that deflate writes
+    fn inflate(bytes: &[u8]) -> Vec<u8> {
+        let mut reader = BitReader { bytes, position: 0 };
+        assert_eq!(reader.bits(1), 1, "there should be a single, final block");
+        assert_eq!(reader.bits(2), 1, "the block should use the fixed Huffman codes");
+        let mut output: Vec<u8> = Vec::new();
+        loop {
+            let symbol = reader.fixed_literal() as usize;
+            match symbol {
+                0..=255 => output.push(symbol as u8),
+                256 => return output,
+                _ => {
+                    let length_code = symbol - 257;
+                    let length = LENGTH_BASES[length_code] as usize + reader.bits(LENGTH_EXTRA_BITS[length_code] as u32) as usize;
+                    let distance_code = reader.code(5) as usize;
+                    let distance = DISTANCE_BASES[distance_code] as usize + reader.bits(DISTANCE_EXTRA_BITS[distance_code] as u32) as usize;
+                    for _ in 0..length {
+                        output.push(output[output.len() - distance]);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Decodes a PNG written by encode_png, checking every chunk on the way.
+    /// Returns: the width, height and rgb bytes of the image
+    fn decode_png(png: &[u8]) -> (usize, usize, Vec<u8>) {
+        assert_eq!(png[..8], SIGNATURE);
+        let mut position = 8;
+        let mut chunks = Vec::new();
+        while position < png.len() {
+            let length = u32::from_be_bytes(png[position..position + 4].try_into().unwrap()) as usize;
+            let chunk_type = &png[position + 4..position + 8];
+            let data = &png[position + 8..position + 8 + length];
+            let crc = u32::from_be_bytes(png[position + 8 + length..position + 12 + length].try_into().unwrap());
+            assert_eq!(crc32(&png[position + 4..position + 8 + length]), crc);
+            chunks.push((chunk_type, data));
+            position += 12 + length;
+        }
+        let types: Vec<&[u8]> = chunks.iter().map(|(chunk_type, _)| *chunk_type).collect();
+        assert_eq!(types, [&b"IHDR"[..], b"IDAT", b"IEND"]);
+        let header = chunks[0].1;
+        let width = u32::from_be_bytes(header[0..4].try_into().unwrap()) as usize;
+        let height = u32::from_be_bytes(header[4..8].try_into().unwrap()) as usize;
+        assert_eq!(header[8..], [8, 2, 0, 0, 0]);
+
+        let stream = chunks[1].1;
+        assert_eq!(u16::from_be_bytes([stream[0], stream[1]]) % 31, 0);
+        let filtered = inflate(&stream[2..stream.len() - 4]);
+        assert_eq!(adler32(&filtered).to_be_bytes(), stream[stream.len() - 4..]);
+
+        let stride = width * BYTES_PER_PIXEL;
+        assert_eq!(filtered.len(), (stride + 1) * height);
+        let mut rgb: Vec<u8> = Vec::with_capacity(stride * height);
+        for (y, row) in filtered.chunks(stride + 1).enumerate() {
+            for i in 0..stride {
+                let left = if i >= BYTES_PER_PIXEL { rgb[y * stride + i - BYTES_PER_PIXEL] } else { 0 };
+                let up = if y > 0 { rgb[(y - 1) * stride + i] } else { 0 };
+                let up_left = if y > 0 && i >= BYTES_PER_PIXEL { rgb[(y - 1) * stride + i - BYTES_PER_PIXEL] } else { 0 };
+                let prediction = match row[0] {
+                    0 => 0,
+                    1 => left,
+                    2 => up,
+                    3 => ((left as u16 + up as u16) / 2) as u8,
+                    4 => paeth(left, up, up_left),
+                    filter => panic!("{} is not a filter type", filter),
+                };
+                rgb.push(row[1 + i].wrapping_add(prediction));
+            }
+        }
+        (width, height, rgb)
+    }
+
+    #[test]
+    fn encode_png_decodes_to_the_same_image() {
+        let (width, height) = (37, 23);
+        // gradients, flat areas and noise, so that every filter and long and short matches are used
+        let mut state: u32 = 7;
+        let mut rgb = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                let pixel = match (x / 10 + y / 8) % 3 {
+                    0 => [(x * 7) as u8, (y * 11) as u8, (x * y) as u8],
+                    1 => [200, 40, 90],
+                    _ => [(state >> 16) as u8, (state >> 8) as u8, state as u8],
+                };
+                rgb.extend_from_slice(&pixel);
+            }
+        }
+        assert_eq!(decode_png(&encode_png(width, height, &rgb)), (width, height, rgb));
+    }
+
+    #[test]
+    fn deflate_round_trips_long_repeats() {
+        let data: Vec<u8> = b"abcabcabcd".iter().cycle().take(100_000).copied().chain(0..=255).collect();
+        assert_eq!(inflate(&deflate(&data)), data);
+        assert_eq!(inflate(&deflate(&[])), []);
+    }
+}
@@ -7,13 +7,19 @@ pub struct ReflectionValue{
     pub b: f32,
 }
 
+impl Default for ReflectionValue {
+    fn default() -> ReflectionValue {
+        ReflectionValue::new()
+    }
+}
+
 impl ReflectionValue{
     pub fn new() -> ReflectionValue{
         ReflectionValue{r: 0.0, g: 0.0, b: 0.0}
     }
 
     pub const fn new_values(r: f32, g: f32, b: f32) -> ReflectionValue{
-        ReflectionValue{r: r, g: g, b: b}
+        ReflectionValue{r, g, b}
     }
 }
 
@@ -0,0 +1,818 @@
+//! Draws the statements of a script: pass 1 works out the knob values of
+//! every frame and the settings of the animation, and pass 2 draws each
+//! frame with them.
+
+use crate::antialias::Antialias;
+use crate::ast::{Axis, Command, Statement};
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::consts;
+use crate::draw::ShadingType;
+use crate::error::{Diagnostic, Diagnostics, Located, MdlError};
+use crate::gif::{GifOptions, GifWriter};
+use crate::image::{Image, PpmFormat};
+use crate::lighting::{Constants, Light, Lighting};
+use crate::matrix::Matrix;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+/// How a script is rendered, on top of what the script itself says
+#[derive(Clone, Debug)]
+pub struct RenderOptions {
+    /// where saved images, animation frames and gifs are written
+    pub output_dir: PathBuf,
+    /// used instead of the basename of the script
+    pub basename: Option<String>,
+    /// the first and last frame to render, when not all of them are. Their
+    /// gif is named after them, like basename0010-0020.gif
+    pub frame_range: Option<(usize, usize)>,
+    /// the screen size to fit the scene into, instead of the size of the script
+    pub size: Option<(usize, usize)>,
+    /// used instead of the antialiasing of the script
+    pub antialias: Option<Antialias>,
+    /// how many frames of an animation are drawn at once
+    pub threads: usize,
+    /// only check the script, without drawing or writing anything
+    pub dry_run: bool,
+    /// called with the name of each frame and gif as it is written
+    pub progress: Option<fn(&str)>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        RenderOptions {
+            output_dir: PathBuf::from("."),
+            basename: None,
+            frame_range: None,
+            size: None,
+            antialias: None,
+            threads: thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1),
+            dry_run: false,
+            progress: None,
+        }
+    }
+}
+
+/// What pass 1 learns about a script
+pub struct Animation {
+    /// the value of every knob in each frame
+    pub frames: Vec<HashMap<String, f32>>,
+    pub basename: String,
+    /// the screen size the scene is laid out for
+    pub scene_size: (usize, usize),
+    pub gif_options: GifOptions,
+    pub antialias: Antialias,
+    /// meshes are read once and reused for every frame
+    pub meshes: HashMap<String, Matrix>,
+}
+
+/// Pass 1: finds the frames, basename and other settings, checks the
+/// commands that do not depend on a frame, and works out the value of
+/// every knob in every frame from set, setknobs, vary and tween
+pub fn analyze(statements: &[Statement], diagnostics: &mut Diagnostics) -> Animation {
+    let mut animation = Animation {
+        frames: vec![HashMap::new()],
+        basename: String::from("output"),
+        scene_size: (consts::SIZE, consts::SIZE),
+        gif_options: GifOptions::default(),
+        antialias: Antialias::none(),
+        meshes: HashMap::new(),
+    };
+    let mut knob_animation_exists = false;
+    let mut frames_exists = false;
+    // every knob the script refers to, for setknobs
+    let mut known_knobs: Vec<&str> = Vec::new();
+    let mut varied_knobs: Vec<&str> = Vec::new();
+    // how many coordinate systems have been pushed, to find unmatched pops
+    let mut stack_depth = 0;
+    for statement in statements {
+        for knob_name in statement.command.knobs() {
+            if !known_knobs.contains(&knob_name) {
+                known_knobs.push(knob_name);
+            }
+        }
+        match &statement.command {
+            Command::Frames { count: 0 } => diagnostics.error(statement, String::from("there must be at least 1 frame")),
+            Command::Frames { count } => {
+                animation.frames = vec![HashMap::new(); *count];
+                frames_exists = true;
+            }
+            Command::Basename { name } => animation.basename = name.clone(),
+            Command::Size { width, height } => match size_error(*width, *height) {
+                Some(message) => diagnostics.error(statement, message),
+                None => animation.scene_size = (*width, *height),
+            },
+            Command::Delay { delay } => animation.gif_options.delay = *delay,
+            Command::Loop { count } => animation.gif_options.loop_count = *count,
+            Command::Dither => animation.gif_options.dither = true,
+            Command::Antialias { antialias } => match antialias_error(antialias) {
+                Some(message) => diagnostics.error(statement, message),
+                None => animation.antialias = *antialias,
+            },
+            Command::Vary { knob, .. } => {
+                knob_animation_exists = true;
+                varied_knobs.push(knob);
+            }
+            Command::Tween { .. } => knob_animation_exists = true,
+            Command::Push => stack_depth += 1,
+            Command::Pop => {
+                if stack_depth == 0 {
+                    diagnostics.error(statement, String::from("pop has no push to match it"));
+                } else {
+                    stack_depth -= 1;
+                }
+            }
+            Command::Mesh { file_name, .. } if !animation.meshes.contains_key(file_name) => {
+                let mut mesh = Matrix::new(0, 0);
+                if let Err(error) = mesh.add_mesh(file_name) {
+                    diagnostics.error(statement, format!("unable to load mesh {} because {}", file_name, error));
+                    mesh = Matrix::new(0, 0);
+                }
+                animation.meshes.insert(file_name.clone(), mesh);
+            }
+            _ => {}
+        }
+    }
+
+    // knob values from set and setknobs, in script order, so that
+    // save_knobs can take a snapshot of them. The final values are
+    // what every frame starts with
+    let mut knobs: HashMap<&str, f32> = HashMap::new();
+    let mut saved_knobs: HashMap<&str, HashMap<&str, f32>> = HashMap::new();
+    for statement in statements {
+        match &statement.command {
+            Command::Set { knob, value } => {
+                knobs.insert(knob, *value);
+            }
+            Command::SetKnobs { value } => {
+                for &knob_name in known_knobs.iter() {
+                    knobs.insert(knob_name, *value);
+                }
+            }
+            Command::SaveKnobs { name } => {
+                saved_knobs.insert(name, knobs.clone());
+            }
+            _ => {}
+        }
+    }
+    for knob_name in known_knobs.iter() {
+        if !knobs.contains_key(knob_name) && !varied_knobs.contains(knob_name) {
+            diagnostics.general_warning(format!("knob {} is never set or varied, so it will be 0", knob_name));
+        }
+    }
+    for frame in animation.frames.iter_mut() {
+        frame.extend(knobs.iter().map(|(&knob_name, &value)| (knob_name.to_owned(), value)));
+    }
+
+    // vary and tween override the set values over their frames
+    if knob_animation_exists && !frames_exists {
+        diagnostics.general_error(String::from("vary or tween used without frame numbers included"));
+        return animation;
+    }
+    let frames = &mut animation.frames;
+    for statement in statements {
+        match &statement.command {
+            Command::Vary { knob, start_frame, end_frame, start_value, end_value, easing } => {
+                if !frames_in_animation(*start_frame, *end_frame, frames.len(), statement, diagnostics) {
+                    continue;
+                }
+                let frame_count = end_frame - start_frame;
+                for (frame_num, frame) in frames.iter_mut().enumerate().take(*end_frame + 1).skip(*start_frame) {
+                    let progress = if frame_count > 0 { (frame_num - start_frame) as f32 / frame_count as f32 } else { 0.0 };
+                    frame.insert(knob.clone(), start_value + (end_value - start_value) * easing.apply(progress));
+                }
+            }
+            Command::Tween { start_frame, end_frame, start_knobs, end_knobs } => {
+                if !frames_in_animation(*start_frame, *end_frame, frames.len(), statement, diagnostics) {
+                    continue;
+                }
+                let mut knob_lists = Vec::new();
+                for knob_list_name in [start_knobs, end_knobs] {
+                    match saved_knobs.get(knob_list_name.as_str()) {
+                        Some(knob_list) => knob_lists.push(knob_list),
+                        None => diagnostics.error(statement, format!("knob list {} was never saved", knob_list_name)),
+                    }
+                }
+                if knob_lists.len() < 2 {
+                    continue;
+                }
+                let (start_knobs, end_knobs) = (knob_lists[0], knob_lists[1]);
+                let frame_count = end_frame - start_frame;
+                for (frame_num, frame) in frames.iter_mut().enumerate().take(*end_frame + 1).skip(*start_frame) {
+                    let progress = if frame_count > 0 { (frame_num - start_frame) as f32 / frame_count as f32 } else { 0.0 };
+                    // a knob only in one of the lists keeps its value across the tween
+                    for &knob_name in start_knobs.keys().chain(end_knobs.keys()) {
+                        let start_value = *start_knobs.get(knob_name).unwrap_or_else(|| &end_knobs[knob_name]);
+                        let end_value = *end_knobs.get(knob_name).unwrap_or(&start_value);
+                        frame.insert(knob_name.to_owned(), start_value + (end_value - start_value) * progress);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    animation
+}
+
+/// Returns: why an antialias factor can not be rendered, if it can not
+fn antialias_error(antialias: &Antialias) -> Option<String> {
+    if antialias.factor == 0 || antialias.factor > Antialias::MAX_FACTOR {
+        return Some(format!("the antialias factor must be from 1 to {}", Antialias::MAX_FACTOR));
+    }
+    None
+}
+
+/// Returns: why a screen size can not be drawn on, if it can not
+fn size_error(width: usize, height: usize) -> Option<String> {
+    if width == 0 || height == 0 {
+        return Some(String::from("the screen must be at least 1 pixel wide and tall"));
+    }
+    None
+}
+
+/// Returns: the first and last frame to render, reporting a range that is
+/// backwards or goes past the end of the animation
+fn frames_to_render(name: &str, animation: &Animation, options: &RenderOptions, diagnostics: &mut Diagnostics) -> (usize, usize) {
+    let frame_count = animation.frames.len();
+    let (first_frame, last_frame) = options.frame_range.unwrap_or((0, frame_count - 1));
+    if last_frame < first_frame {
+        diagnostics.general_error(format!("unable to render frames {} to {} because the first frame is after the last", first_frame, last_frame));
+    }
+    if last_frame >= frame_count {
+        diagnostics.general_error(format!("unable to render frame {} because {} only has {} frames", last_frame, name, frame_count));
+    }
+    (first_frame, last_frame)
+}
+
+/// whether a vary or tween runs forwards and ends before the last frame
+fn frames_in_animation(start_frame: usize, end_frame: usize, frame_count: usize, at: &Statement, diagnostics: &mut Diagnostics) -> bool {
+    if end_frame < start_frame {
+        diagnostics.error(at, String::from("start frame number is greater than end frame number"));
+        return false;
+    }
+    if end_frame >= frame_count {
+        diagnostics.error(at, String::from("end frame number is past the last frame"));
+        return false;
+    }
+    true
+}
+
+/// Renders the statements of the script called name, writing a gif and its
+/// frames for an animation.
+/// Returns: the warnings about the script, or every diagnostic found in
+/// it if it could not be rendered
+pub fn render(name: &str, statements: &[Statement], options: &RenderOptions, mut diagnostics: Diagnostics) -> Result<Vec<Diagnostic>, MdlError> {
+    let mut animation = analyze(statements, &mut diagnostics);
+    if let Some(antialias) = options.antialias {
+        match antialias_error(&antialias) {
+            Some(message) => diagnostics.general_error(message),
+            None => animation.antialias = antialias,
+        }
+    }
+    let frames = &animation.frames;
+    let basename = options.basename.clone().unwrap_or_else(|| animation.basename.clone());
+    let (width, height) = options.size.unwrap_or(animation.scene_size);
+    if let Some(message) = size_error(width, height) {
+        diagnostics.general_error(message);
+    }
+    let (first_frame, last_frame) = frames_to_render(name, &animation, options, &mut diagnostics);
+    // nothing is drawn unless the whole script makes sense
+    if diagnostics.has_errors() {
+        return Err(MdlError::new(name, diagnostics));
+    }
+    if options.dry_run {
+        return Ok(diagnostics.list);
+    }
+    if let Err(error) = fs::create_dir_all(&options.output_dir) {
+        diagnostics.general_error(format!("unable to create {} because {}", options.output_dir.display(), error));
+        return Err(MdlError::new(name, diagnostics));
+    }
+    let animation_dir = options.output_dir.join("animation");
+    // the gif is written as each frame is rendered
+    let mut gif = None;
+    // a partial render gets a gif of its own, so it does not replace the whole animation
+    let gif_basename = match options.frame_range {
+        Some(_) => format!("{}{:04}-{:04}", basename, first_frame, last_frame),
+        None => basename.clone(),
+    };
+    let gif_name = options.output_dir.join(gif_basename + ".gif").to_string_lossy().into_owned();
+    if frames.len() > 1 {
+        if let Err(error) = fs::create_dir_all(&animation_dir) {
+            diagnostics.general_error(format!("unable to create {} because {}", animation_dir.display(), error));
+            return Err(MdlError::new(name, diagnostics));
+        }
+        // frames outside of a partial render are kept from earlier renders
+        if options.frame_range.is_none() {
+            clean_animation_directory(&animation_dir, &mut diagnostics);
+        }
+        match GifWriter::create(&gif_name, width, height, animation.gif_options) {
+            Ok(writer) => gif = Some(writer),
+            Err(error) => diagnostics.general_error(format!("unable to create {} because {}", gif_name, error)),
+        }
+    }
+    // pass 2
+    if frames.len() > 1 {
+        render_frames(statements, &animation, (first_frame, last_frame), (width, height), Some(&options.output_dir), options.threads, |frame_num, screen, frame_diagnostics| {
+            diagnostics.extend(frame_diagnostics);
+            write_frame(&animation_dir, &basename, frame_num, &screen, &mut gif, options.progress, &mut diagnostics);
+        });
+    } else {
+        draw_frame(statements, &animation, 0, (width, height), Some(&options.output_dir), &mut diagnostics);
+    }
+    if let Some(gif) = gif {
+        if let Some(progress) = options.progress {
+            progress(&gif_name);
+        }
+        if let Err(error) = gif.finish() {
+            diagnostics.general_error(format!("unable to finish {} because {}", gif_name, error));
+        }
+    }
+    if diagnostics.has_errors() {
+        return Err(MdlError::new(name, diagnostics));
+    }
+    Ok(diagnostics.list)
+}
+
+/// Draws the frames of the statements without writing them anywhere, for
+/// programs that want the images themselves. name is only used in diagnostics,
+/// and diagnostics holds any that were found before rendering started
+/// Returns: the frames in order along with the warnings about the statements,
+/// or every diagnostic found in them if they could not be rendered
+pub fn render_images(name: &str, statements: &[Statement], options: &RenderOptions, mut diagnostics: Diagnostics) -> Result<(Vec<Image>, Vec<Diagnostic>), MdlError> {
+    let mut animation = analyze(statements, &mut diagnostics);
+    if let Some(antialias) = options.antialias {
+        match antialias_error(&antialias) {
+            Some(message) => diagnostics.general_error(message),
+            None => animation.antialias = antialias,
+        }
+    }
+    let size = options.size.unwrap_or(animation.scene_size);
+    if let Some(message) = size_error(size.0, size.1) {
+        diagnostics.general_error(message);
+    }
+    let (first_frame, last_frame) = frames_to_render(name, &animation, options, &mut diagnostics);
+    if diagnostics.has_errors() {
+        return Err(MdlError::new(name, diagnostics));
+    }
+    let mut images = Vec::new();
+    if !options.dry_run {
+        render_frames(statements, &animation, (first_frame, last_frame), size, None, options.threads, |_, screen, frame_diagnostics| {
+            diagnostics.extend(frame_diagnostics);
+            images.push(screen);
+        });
+    }
+    if diagnostics.has_errors() {
+        return Err(MdlError::new(name, diagnostics));
+    }
+    Ok((images, diagnostics.list))
+}
+
+/// Draws frames first_frame to last_frame of an animation, up to threads of
+/// them at once, each onto its own screen. Frames can finish out of order,
+/// so each one waits until the frames before it have been handed to write
+fn render_frames<F: FnMut(usize, Image, Diagnostics)>(statements: &[Statement], animation: &Animation, (first_frame, last_frame): (usize, usize), (width, height): (usize, usize), output_dir: Option<&Path>, threads: usize, mut write: F) {
+    let next_frame = AtomicUsize::new(first_frame);
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, last_frame - first_frame + 1) {
+            let sender = sender.clone();
+            let next_frame = &next_frame;
+            scope.spawn(move || loop {
+                let frame_num = next_frame.fetch_add(1, Ordering::Relaxed);
+                if frame_num > last_frame {
+                    break;
+                }
+                let mut diagnostics = Diagnostics::new();
+                let screen = draw_frame(statements, animation, frame_num, (width, height), output_dir, &mut diagnostics);
+                if sender.send((frame_num, screen, diagnostics)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+        let mut finished = BTreeMap::new();
+        let mut next_to_write = first_frame;
+        for (frame_num, screen, diagnostics) in receiver {
+            finished.insert(frame_num, (screen, diagnostics));
+            while let Some((screen, diagnostics)) = finished.remove(&next_to_write) {
+                write(next_to_write, screen, diagnostics);
+                next_to_write += 1;
+            }
+        }
+    });
+}
+
+/// Draws one frame of the animation, supersampling it if it is antialiased.
+/// Returns: the frame at the given screen size
+fn draw_frame(statements: &[Statement], animation: &Animation, frame_num: usize, (width, height): (usize, usize), output_dir: Option<&Path>, diagnostics: &mut Diagnostics) -> Image {
+    let antialias = &animation.antialias;
+    let mut screen = Image::new(width * antialias.factor, height * antialias.factor);
+    render_frame(statements, animation, frame_num, &mut screen, output_dir, diagnostics);
+    if antialias.is_enabled() {
+        return screen.downsample(antialias);
+    }
+    screen
+}
+
+/// Pass 2: draws one frame of the animation onto screen, which is
+/// supersampled if the animation is antialiased. save and display only
+/// happen for scripts that are not animated, and not at all without an
+/// output_dir, when the images are returned instead of written
+pub fn render_frame(statements: &[Statement], animation: &Animation, frame_num: usize, screen: &mut Image, output_dir: Option<&Path>, diagnostics: &mut Diagnostics) {
+    let knobs = &animation.frames[frame_num];
+    let color = Color::new_color(0, 255, 0);
+    let mut cstack: Vec<Matrix> = vec![Matrix::identity()];
+    let mut lighting = Lighting::new();
+    let mut shading = ShadingType::Flat;
+    let mut coord_systems: HashMap<&str, Matrix> = HashMap::new();
+    // the camera, lights, ambient light and constants apply to the whole
+    // frame, wherever they are in the script
+    let mut camera = Camera::new();
+    let mut focal = None;
+    for statement in statements {
+        match &statement.command {
+            Command::Constants { name, constants } => {
+                lighting.add_constants(name, constants.clone());
+            }
+            Command::Light { name, color, location, color_knob, location_knob } => {
+                let color_scale = knob_scale(knobs, color_knob);
+                let location_scale = knob_scale(knobs, location_knob);
+                let light = Light::new_values(
+                    color[0] * color_scale,
+                    color[1] * color_scale,
+                    color[2] * color_scale,
+                    location[0] * location_scale,
+                    location[1] * location_scale,
+                    location[2] * location_scale,
+                );
+                lighting.add_light(name, light);
+            }
+            Command::Ambient { color } => {
+                lighting.set_ambient(Color::from_f32(color[0], color[1], color[2]));
+            }
+            Command::Camera { eye, aim, eye_knob, aim_knob } => {
+                let eye_scale = knob_scale(knobs, eye_knob);
+                let aim_scale = knob_scale(knobs, aim_knob);
+                camera = Camera::look_at(
+                    eye.iter().map(|value| value * eye_scale).collect(),
+                    aim.iter().map(|value| value * aim_scale).collect(),
+                );
+            }
+            Command::Focal { focal: value } => focal = Some(*value),
+            _ => {}
+        }
+    }
+    if let Some(focal) = focal {
+        camera.set_focal(focal);
+    }
+    camera.set_scene_size(animation.scene_size.0, animation.scene_size.1);
+
+    for statement in statements {
+        match &statement.command {
+            Command::Shading { shading: shading_type } => shading = *shading_type,
+            Command::Specular { model } => lighting.specular_model = *model,
+            Command::DepthTest { test } => screen.depth_test = *test,
+            Command::DepthBias { bias } => screen.depth_bias = *bias,
+            Command::Push => {
+                cstack.push(cstack.last().unwrap().clone());
+            }
+            Command::Pop => {
+                // the bottom of the stack stays, since unmatched pops are errors
+                if cstack.len() > 1 {
+                    cstack.pop();
+                }
+            }
+            Command::Move { offset, knob } => {
+                let mut translate = Matrix::make_translate_with_scale(offset[0], offset[1], offset[2], knob_scale(knobs, knob));
+                translate.multiply_matrixes(&cstack.pop().unwrap());
+                cstack.push(translate);
+            }
+            Command::Scale { scale, knob } => {
+                let mut scale = Matrix::make_scale_with_scale(scale[0], scale[1], scale[2], knob_scale(knobs, knob));
+                scale.multiply_matrixes(&cstack.pop().unwrap());
+                cstack.push(scale);
+            }
+            Command::Rotate { axis, degrees, knob } => {
+                let degrees = degrees * knob_scale(knobs, knob);
+                let mut rot = match axis {
+                    Axis::X => Matrix::make_rot_x(degrees),
+                    Axis::Y => Matrix::make_rot_y(degrees),
+                    Axis::Z => Matrix::make_rot_z(degrees),
+                };
+                rot.multiply_matrixes(&cstack.pop().unwrap());
+                cstack.push(rot);
+            }
+            Command::SaveCoordSystem { name } => {
+                coord_systems.insert(name, cstack.last().unwrap().clone());
+            }
+            Command::Sphere { constants: constants_name, center, radius, coord_system } => {
+                let mut polygons = Matrix::new(0, 0);
+                polygons.add_sphere(center[0], center[1], center[2], *radius, consts::STEP_3D);
+                let default_constants = Constants::default();
+                let lighting_constants = constants(constants_name, &lighting, &default_constants, statement, diagnostics);
+                polygons.multiply_matrixes(coordinate_system(coord_system, &coord_systems, &cstack, statement, diagnostics));
+                screen.draw_polygons(&polygons, &color, &camera, &lighting, lighting_constants, &shading);
+            }
+            Command::Box { constants: constants_name, corner, size, coord_system } => {
+                let mut polygons = Matrix::new(0, 0);
+                polygons.add_box(corner[0], corner[1], corner[2], size[0], size[1], size[2]);
+                let default_constants = Constants::default();
+                let lighting_constants = constants(constants_name, &lighting, &default_constants, statement, diagnostics);
+                polygons.multiply_matrixes(coordinate_system(coord_system, &coord_systems, &cstack, statement, diagnostics));
+                screen.draw_polygons(&polygons, &color, &camera, &lighting, lighting_constants, &shading);
+            }
+            Command::Torus { constants: constants_name, center, minor_radius, major_radius, coord_system } => {
+                let mut polygons = Matrix::new(0, 0);
+                polygons.add_torus(center[0], center[1], center[2], *minor_radius, *major_radius, consts::STEP_3D);
+                let default_constants = Constants::default();
+                let lighting_constants = constants(constants_name, &lighting, &default_constants, statement, diagnostics);
+                polygons.multiply_matrixes(coordinate_system(coord_system, &coord_systems, &cstack, statement, diagnostics));
+                screen.draw_polygons(&polygons, &color, &camera, &lighting, lighting_constants, &shading);
+            }
+            Command::Mesh { constants: constants_name, file_name, coord_system } => {
+                let mesh = match animation.meshes.get(file_name) {
+                    Some(mesh) if !mesh.matrix_array.is_empty() => mesh,
+                    // the mesh could not be loaded, which pass 1 reported
+                    _ => continue,
+                };
+                let mut polygons = mesh.clone();
+                let default_constants = Constants::default();
+                let lighting_constants = constants(constants_name, &lighting, &default_constants, statement, diagnostics);
+                polygons.multiply_matrixes(coordinate_system(coord_system, &coord_systems, &cstack, statement, diagnostics));
+                screen.draw_polygons(&polygons, &color, &camera, &lighting, lighting_constants, &shading);
+            }
+            Command::Line { start, start_coord_system, end, end_coord_system, .. } => {
+                let mut start_point = Matrix::new(0, 0);
+                start_point.add_point(start[0], start[1], start[2]);
+                start_point.multiply_matrixes(coordinate_system(start_coord_system, &coord_systems, &cstack, statement, diagnostics));
+                let mut end_point = Matrix::new(0, 0);
+                end_point.add_point(end[0], end[1], end[2]);
+                end_point.multiply_matrixes(coordinate_system(end_coord_system, &coord_systems, &cstack, statement, diagnostics));
+                let mut edges = Matrix::new(0, 0);
+                edges.add_edge(
+                    start_point.matrix_array[0][0],
+                    start_point.matrix_array[1][0],
+                    start_point.matrix_array[2][0],
+                    end_point.matrix_array[0][0],
+                    end_point.matrix_array[1][0],
+                    end_point.matrix_array[2][0],
+                );
+                screen.draw_lines(&edges, &color, &camera);
+            }
+            Command::Display => {
+                if animation.frames.len() <= 1 {
+                    match output_dir {
+                        Some(_) => {
+                            let result = if animation.antialias.is_enabled() { screen.downsample(&animation.antialias).display() } else { screen.display() };
+                            if let Err(error) = result {
+                                diagnostics.error(statement, format!("unable to display the image because {}", error));
+                            }
+                        }
+                        None => diagnostics.warning(statement, String::from("the image is not displayed because it is returned instead")),
+                    }
+                }
+            }
+            Command::Save { file_name } => {
+                if animation.frames.len() <= 1 {
+                    match output_dir {
+                        Some(output_dir) => {
+                            let file_name = output_dir.join(file_name).to_string_lossy().into_owned();
+                            let result = if animation.antialias.is_enabled() { screen.downsample(&animation.antialias).save(&file_name) } else { screen.save(&file_name) };
+                            if let Err(error) = result {
+                                diagnostics.error(statement, format!("unable to save {} because {}", file_name, error));
+                            }
+                        }
+                        None => diagnostics.warning(statement, format!("{} is not saved because the image is returned instead", file_name)),
+                    }
+                }
+            }
+            // handled in pass 1 or before drawing
+            Command::Frames { .. } | Command::Basename { .. } | Command::Size { .. } | Command::Delay { .. } | Command::Loop { .. } |
+            Command::Dither | Command::Antialias { .. } | Command::Set { .. } | Command::SetKnobs { .. } | Command::SaveKnobs { .. } | Command::Vary { .. } |
+            Command::Tween { .. } | Command::Camera { .. } | Command::Focal { .. } | Command::Constants { .. } | Command::Light { .. } |
+            Command::Ambient { .. } => {}
+        }
+    }
+}
+
+/// the value of a knob in the given frame, or 0 if it was never set
+fn knob_value(frame: &HashMap<String, f32>, knob_name: &str) -> f32 {
+    *frame.get(knob_name).unwrap_or(&0.0)
+}
+
+/// how much a command is scaled by its knob, if it has one
+fn knob_scale(frame: &HashMap<String, f32>, knob: &Option<String>) -> f32 {
+    match knob {
+        Some(knob_name) => knob_value(frame, knob_name),
+        None => 1.0,
+    }
+}
+
+/// the lighting constants with the given name, or the default ones if there is no name
+fn constants<'c>(name: &Option<String>, lighting: &'c Lighting, default_constants: &'c Constants, at: &impl Located, diagnostics: &mut Diagnostics) -> &'c Constants {
+    if let Some(name) = name {
+        match lighting.get_constants(name) {
+            Some(constants) => return constants,
+            None => diagnostics.warning(at, format!("constants {} were never defined, the default ones will be used instead", name)),
+        }
+    }
+    default_constants
+}
+
+/// the transformation to draw with: the named coordinate system if there is
+/// one, otherwise the top of the coordinate system stack
+fn coordinate_system<'m>(name: &Option<String>, coord_systems: &'m HashMap<&str, Matrix>, cstack: &'m [Matrix], at: &impl Located, diagnostics: &mut Diagnostics) -> &'m Matrix {
+    if let Some(name) = name {
+        match coord_systems.get(name.as_str()) {
+            Some(coord_system) => return coord_system,
+            None => diagnostics.warning(at, format!("coordinate system {} was never saved, the current one will be used instead", name)),
+        }
+    }
+    cstack.last().unwrap()
+}
+
+/// writes a finished frame of an animation to its file and the gif
+fn write_frame(animation_dir: &Path, basename: &str, frame_num: usize, screen: &Image, gif: &mut Option<GifWriter<BufWriter<File>>>, progress: Option<fn(&str)>, diagnostics: &mut Diagnostics) {
+    let filename = animation_dir.join(basename.to_owned() + &format!("{:04}", frame_num) + ".ppm").to_string_lossy().into_owned();
+    if let Some(progress) = progress {
+        progress(&filename);
+    }
+    if let Err(error) = screen.create_file(&filename, PpmFormat::Binary) {
+        diagnostics.general_error(format!("unable to save {} because {}", filename, error));
+    }
+    if let Some(writer) = gif {
+        if let Err(error) = writer.add_frame(&screen.rgb_bytes()) {
+            diagnostics.general_error(format!("unable to add {} to the gif because {}", filename, error));
+            *gif = None;
+        }
+    }
+}
+
+/// removes the frames of earlier renders from the animation directory
+fn clean_animation_directory(animation_dir: &Path, diagnostics: &mut Diagnostics) {
+    let entries = match fs::read_dir(animation_dir) {
+        Ok(entries) => entries,
+        Err(error) => {
+            diagnostics.general_error(format!("unable to read {} because {}", animation_dir.display(), error));
+            return;
+        }
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().is_some_and(|extension| extension == "ppm") {
+            if let Err(error) = fs::remove_file(&path) {
+                diagnostics.general_error(format!("unable to delete {} because {}", path.display(), error));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::Scene;
+
+    /// checks that rendering the scene fails with an error saying message
+    fn assert_render_error(scene: &Scene, options: &RenderOptions, message: &str) {
+        let error = scene.render(options).unwrap_err();
+        assert!(error.diagnostics.iter().any(|diagnostic| diagnostic.message.contains(message)), "{:?}", error.diagnostics);
+    }
+
+    #[test]
+    fn backwards_frame_range_is_reported() {
+        let mut scene = Scene::new();
+        scene.frames(10).add_sphere([250.0, 250.0, 0.0], 50.0);
+        let options = RenderOptions { frame_range: Some((5, 2)), ..RenderOptions::default() };
+        assert_render_error(&scene, &options, "first frame is after the last");
+    }
+
+    #[test]
+    fn backwards_vary_and_tween_are_reported() {
+        let mut scene = Scene::new();
+        scene.frames(10).vary("spin", (5, 2), (0.0, 1.0));
+        assert_render_error(&scene, &RenderOptions::default(), "start frame number is greater than end frame number");
+        let mut scene = Scene::new();
+        scene.frames(10).set_knob("spin", 0.0).save_knobs("a").save_knobs("b").tween((5, 2), "a", "b");
+        assert_render_error(&scene, &RenderOptions::default(), "start frame number is greater than end frame number");
+    }
+
+    #[test]
+    fn empty_screen_is_reported() {
+        let mut scene = Scene::new();
+        scene.size(0, 0).add_line([0.0, 0.0, 0.0], [10.0, 10.0, 0.0]);
+        assert_render_error(&scene, &RenderOptions::default(), "at least 1 pixel wide and tall");
+        let mut scene = Scene::new();
+        scene.add_line([0.0, 0.0, 0.0], [10.0, 10.0, 0.0]);
+        let options = RenderOptions { size: Some((100, 0)), ..RenderOptions::default() };
+        assert_render_error(&scene, &options, "at least 1 pixel wide and tall");
+    }
+
+    #[test]
+    fn rendering_to_images_does_not_save() {
+        let file_name = std::env::temp_dir().join(format!("hw_11_not_saved_{}.png", std::process::id())).to_string_lossy().into_owned();
+        let mut scene = Scene::new();
+        scene.add_sphere([250.0, 250.0, 0.0], 50.0).save(&file_name);
+        let (images, warnings) = scene.render(&RenderOptions::default()).unwrap();
+        assert_eq!(images.len(), 1);
+        assert!(!Path::new(&file_name).exists());
+        assert!(warnings.iter().any(|warning| warning.message.contains("is not saved because the image is returned instead")));
+    }
+
+    #[test]
+    fn analyze_works_out_the_knobs_of_every_frame() {
+        let script = "frames 5\nbasename spin\nset zoom 2\nvary spin 0 4 0 1\n\
+                      set fade 0\nsave_knobs dark\nset fade 10\nsave_knobs light\ntween 2 4 dark light\n";
+        let mut diagnostics = Diagnostics::new();
+        let statements = crate::parser::parse_statements(script, &mut diagnostics).unwrap();
+        let animation = analyze(&statements, &mut diagnostics);
+        assert!(diagnostics.list.is_empty(), "{:?}", diagnostics.list);
+        assert_eq!(animation.basename, "spin");
+        assert_eq!(animation.frames.len(), 5);
+        let values = |knob_name: &str| animation.frames.iter().map(|frame| frame[knob_name]).collect::<Vec<f32>>();
+        assert_eq!(values("zoom"), [2.0; 5]);
+        assert_eq!(values("spin"), [0.0, 0.25, 0.5, 0.75, 1.0]);
+        // the last set holds until the tween takes over
+        assert_eq!(values("fade"), [10.0, 10.0, 0.0, 5.0, 10.0]);
+    }
+
+    #[test]
+    fn lighting_applies_to_shapes_before_it() {
+        let shiny = Constants::new(0.2, 0.5, 0.8, 0.2, 0.5, 0.8, 0.2, 0.5, 0.8, 0.0, 0.0, 0.0);
+        let mut lit_first = Scene::new();
+        lit_first
+            .add_light("key", [255.0, 0.0, 0.0], [0.5, 0.75, 1.0])
+            .ambient([0.0, 0.0, 80.0])
+            .add_constants("shiny", shiny.clone())
+            .add_sphere([250.0, 250.0, 0.0], 100.0)
+            .with_constants("shiny");
+        let mut lit_last = Scene::new();
+        lit_last
+            .add_sphere([250.0, 250.0, 0.0], 100.0)
+            .with_constants("shiny")
+            .add_light("key", [255.0, 0.0, 0.0], [0.5, 0.75, 1.0])
+            .ambient([0.0, 0.0, 80.0])
+            .add_constants("shiny", shiny);
+        let (first_images, _) = lit_first.render(&RenderOptions::default()).unwrap();
+        let (last_images, warnings) = lit_last.render(&RenderOptions::default()).unwrap();
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert!(first_images[0].screen == last_images[0].screen);
+    }
+
+    #[test]
+    fn parallel_frames_are_written_in_order() {
+        let script = "frames 8\nvary slide 0 7 0 1\nmove 200 0 0 slide\nsphere 150 250 0 50\n";
+        let mut diagnostics = Diagnostics::new();
+        let statements = crate::parser::parse_statements(script, &mut diagnostics).unwrap();
+        let animation = analyze(&statements, &mut diagnostics);
+        let written = |threads| {
+            let mut frames = Vec::new();
+            render_frames(&statements, &animation, (0, 7), (100, 100), None, threads, |frame_num, screen, _| frames.push((frame_num, screen.screen)));
+            frames
+        };
+        let serial = written(1);
+        let parallel = written(4);
+        assert_eq!(parallel.iter().map(|(frame_num, _)| *frame_num).collect::<Vec<usize>>(), (0..8).collect::<Vec<usize>>());
+        assert!(serial == parallel);
+    }
+
+    #[test]
+    fn partial_render_keeps_the_whole_gif() {
+        let output_dir = std::env::temp_dir().join(format!("hw_11_partial_{}", std::process::id()));
+        let mut scene = Scene::new();
+        scene.frames(4).basename("spin").add_sphere([250.0, 250.0, 0.0], 50.0);
+        let options = RenderOptions { output_dir: output_dir.clone(), frame_range: Some((1, 2)), ..RenderOptions::default() };
+        scene.write(&options).unwrap();
+        let partial_exists = output_dir.join("spin0001-0002.gif").exists();
+        let whole_exists = output_dir.join("spin.gif").exists();
+        fs::remove_dir_all(&output_dir).unwrap();
+        assert!(partial_exists);
+        assert!(!whole_exists);
+    }
+
+    #[test]
+    fn size_option_fits_the_scene_onto_the_screen() {
+        let mut scene = Scene::new();
+        scene.size(100, 100).add_sphere([50.0, 50.0, 0.0], 20.0);
+        let options = RenderOptions { size: Some((300, 100)), ..RenderOptions::default() };
+        let (images, _) = scene.render(&options).unwrap();
+        let screen = &images[0].screen;
+        assert_eq!((screen[0].len(), screen.len()), (300, 100));
+        // the square scene is centered between empty bands on either side
+        let background = screen[50][10];
+        assert_ne!(screen[50][150], background);
+        assert_eq!(screen[50][95], background);
+        assert_eq!(screen[50][205], background);
+    }
+
+    #[test]
+    fn zero_frames_are_reported() {
+        let mut scene = Scene::new();
+        scene.frames(0).add_sphere([250.0, 250.0, 0.0], 50.0);
+        assert_render_error(&scene, &RenderOptions::default(), "at least 1 frame");
+    }
+}
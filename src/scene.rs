@@ -0,0 +1,312 @@
+//! Builds the statements of a scene in Rust instead of reading them from an
+//! MDL script. Each method adds the command of the same name, so
+//!
+//! ```no_run
+//! use hw_11::{Axis, RenderOptions, Scene};
+//!
+//! let mut scene = Scene::new();
+//! scene.frames(60).basename("spin")
+//!     .vary("turn", (0, 59), (0.0, 1.0))
+//!     .translate([250.0, 250.0, 0.0])
+//!     .rotate(Axis::Y, 360.0).knob("turn")
+//!     .add_torus([0.0, 0.0, 0.0], 20.0, 100.0);
+//! let (frames, _warnings) = scene.render(&RenderOptions::default()).unwrap();
+//! ```
+//!
+//! draws the same thing as the script
+//!
+//! ```text
+//! frames 60
+//! basename spin
+//! vary turn 0 59 0 1
+//! move 250 250 0
+//! rotate y 360 turn
+//! torus 0 0 0 20 100
+//! ```
+
+use crate::antialias::{Antialias, Filter};
+use crate::ast::{Axis, Command, Statement};
+use crate::draw::ShadingType;
+use crate::easing::Easing;
+use crate::error::{Diagnostic, Diagnostics, MdlError};
+use crate::image::{DepthTest, Image};
+use crate::lighting::{Constants, SpecularModel};
+use crate::render::{self, RenderOptions};
+
+/// A scene built one command at a time
+#[derive(Clone, Debug, Default)]
+pub struct Scene {
+    pub statements: Vec<Statement>,
+    /// mistakes in how the scene was built, reported when it is rendered
+    diagnostics: Diagnostics,
+}
+
+impl Scene {
+    pub fn new() -> Scene {
+        Scene { statements: Vec::new(), diagnostics: Diagnostics::new() }
+    }
+
+    /// adds any command, for the ones without a method of their own
+    pub fn command(&mut self, command: Command) -> &mut Scene {
+        self.statements.push(Statement::new(command));
+        self
+    }
+
+    pub fn frames(&mut self, count: usize) -> &mut Scene {
+        self.command(Command::Frames { count })
+    }
+
+    pub fn basename(&mut self, name: &str) -> &mut Scene {
+        self.command(Command::Basename { name: name.to_owned() })
+    }
+
+    /// the screen size the scene is laid out for
+    pub fn size(&mut self, width: usize, height: usize) -> &mut Scene {
+        self.command(Command::Size { width, height })
+    }
+
+    /// supersamples each pixel factor by factor times, then filters it down
+    pub fn antialias(&mut self, factor: usize, filter: Filter) -> &mut Scene {
+        self.command(Command::Antialias { antialias: Antialias::new(factor, filter) })
+    }
+
+    pub fn set_knob(&mut self, knob: &str, value: f32) -> &mut Scene {
+        self.command(Command::Set { knob: knob.to_owned(), value })
+    }
+
+    pub fn set_knobs(&mut self, value: f32) -> &mut Scene {
+        self.command(Command::SetKnobs { value })
+    }
+
+    pub fn save_knobs(&mut self, name: &str) -> &mut Scene {
+        self.command(Command::SaveKnobs { name: name.to_owned() })
+    }
+
+    /// Inputs: frames: the first and last frame of the vary
+    ///         values: the value of the knob in those frames
+    pub fn vary(&mut self, knob: &str, frames: (usize, usize), values: (f32, f32)) -> &mut Scene {
+        self.vary_with_easing(knob, frames, values, Easing::Linear)
+    }
+
+    pub fn vary_with_easing(&mut self, knob: &str, frames: (usize, usize), values: (f32, f32), easing: Easing) -> &mut Scene {
+        self.command(Command::Vary {
+            knob: knob.to_owned(),
+            start_frame: frames.0,
+            end_frame: frames.1,
+            start_value: values.0,
+            end_value: values.1,
+            easing,
+        })
+    }
+
+    pub fn tween(&mut self, frames: (usize, usize), start_knobs: &str, end_knobs: &str) -> &mut Scene {
+        self.command(Command::Tween {
+            start_frame: frames.0,
+            end_frame: frames.1,
+            start_knobs: start_knobs.to_owned(),
+            end_knobs: end_knobs.to_owned(),
+        })
+    }
+
+    pub fn camera(&mut self, eye: [f32; 3], aim: [f32; 3]) -> &mut Scene {
+        self.command(Command::Camera { eye, aim, eye_knob: None, aim_knob: None })
+    }
+
+    pub fn focal(&mut self, focal: f32) -> &mut Scene {
+        self.command(Command::Focal { focal })
+    }
+
+    pub fn add_constants(&mut self, name: &str, constants: Constants) -> &mut Scene {
+        self.command(Command::Constants { name: name.to_owned(), constants })
+    }
+
+    pub fn add_light(&mut self, name: &str, color: [f32; 3], location: [f32; 3]) -> &mut Scene {
+        self.command(Command::Light {
+            name: name.to_owned(),
+            color,
+            location,
+            color_knob: None,
+            location_knob: None,
+        })
+    }
+
+    pub fn ambient(&mut self, color: [f32; 3]) -> &mut Scene {
+        self.command(Command::Ambient { color })
+    }
+
+    pub fn shading(&mut self, shading: ShadingType) -> &mut Scene {
+        self.command(Command::Shading { shading })
+    }
+
+    pub fn specular(&mut self, model: SpecularModel) -> &mut Scene {
+        self.command(Command::Specular { model })
+    }
+
+    pub fn depth_test(&mut self, test: DepthTest) -> &mut Scene {
+        self.command(Command::DepthTest { test })
+    }
+
+    /// added to the depth of everything drawn after it, toward the camera
+    pub fn depth_bias(&mut self, bias: f32) -> &mut Scene {
+        self.command(Command::DepthBias { bias })
+    }
+
+    pub fn push(&mut self) -> &mut Scene {
+        self.command(Command::Push)
+    }
+
+    pub fn pop(&mut self) -> &mut Scene {
+        self.command(Command::Pop)
+    }
+
+    /// the move command, which is a keyword in Rust
+    pub fn translate(&mut self, offset: [f32; 3]) -> &mut Scene {
+        self.command(Command::Move { offset, knob: None })
+    }
+
+    pub fn scale(&mut self, scale: [f32; 3]) -> &mut Scene {
+        self.command(Command::Scale { scale, knob: None })
+    }
+
+    pub fn rotate(&mut self, axis: Axis, degrees: f32) -> &mut Scene {
+        self.command(Command::Rotate { axis, degrees, knob: None })
+    }
+
+    pub fn save_coord_system(&mut self, name: &str) -> &mut Scene {
+        self.command(Command::SaveCoordSystem { name: name.to_owned() })
+    }
+
+    pub fn add_sphere(&mut self, center: [f32; 3], radius: f32) -> &mut Scene {
+        self.command(Command::Sphere { constants: None, center, radius, coord_system: None })
+    }
+
+    /// corner is the upper-left-front corner of the box
+    pub fn add_box(&mut self, corner: [f32; 3], size: [f32; 3]) -> &mut Scene {
+        self.command(Command::Box { constants: None, corner, size, coord_system: None })
+    }
+
+    pub fn add_torus(&mut self, center: [f32; 3], minor_radius: f32, major_radius: f32) -> &mut Scene {
+        self.command(Command::Torus {
+            constants: None,
+            center,
+            minor_radius,
+            major_radius,
+            coord_system: None,
+        })
+    }
+
+    pub fn add_line(&mut self, start: [f32; 3], end: [f32; 3]) -> &mut Scene {
+        self.command(Command::Line {
+            constants: None,
+            start,
+            start_coord_system: None,
+            end,
+            end_coord_system: None,
+        })
+    }
+
+    /// Inputs: file_name: an obj file, read when the scene is rendered
+    pub fn add_mesh(&mut self, file_name: &str) -> &mut Scene {
+        self.command(Command::Mesh { constants: None, file_name: file_name.to_owned(), coord_system: None })
+    }
+
+    pub fn save(&mut self, file_name: &str) -> &mut Scene {
+        self.command(Command::Save { file_name: file_name.to_owned() })
+    }
+
+    /// Scales the last command by a knob: a move, scale or rotate, or the
+    /// location of a light or the eye of a camera. The color of a light and
+    /// the aim of a camera can only be given a knob through `command`.
+    /// If the last command cannot have a knob, rendering the scene fails
+    pub fn knob(&mut self, knob_name: &str) -> &mut Scene {
+        let knob = Some(knob_name.to_owned());
+        match self.statements.last_mut().map(|statement| &mut statement.command) {
+            Some(Command::Move { knob: command_knob, .. }) | Some(Command::Scale { knob: command_knob, .. }) | Some(Command::Rotate { knob: command_knob, .. }) => *command_knob = knob,
+            Some(Command::Light { location_knob, .. }) => *location_knob = knob,
+            Some(Command::Camera { eye_knob, .. }) => *eye_knob = knob,
+            _ => self.diagnostics.general_error(format!("knob {} follows a command that cannot be scaled by a knob", knob_name)),
+        }
+        self
+    }
+
+    /// Draws the last shape with the lighting constants of the given name.
+    /// If the last command is not a shape, rendering the scene fails
+    pub fn with_constants(&mut self, name: &str) -> &mut Scene {
+        let constants_name = Some(name.to_owned());
+        match self.statements.last_mut().map(|statement| &mut statement.command) {
+            Some(Command::Sphere { constants, .. }) | Some(Command::Box { constants, .. }) | Some(Command::Torus { constants, .. }) |
+            Some(Command::Line { constants, .. }) | Some(Command::Mesh { constants, .. }) => *constants = constants_name,
+            _ => self.diagnostics.general_error(format!("constants {} follow a command that is not a shape", name)),
+        }
+        self
+    }
+
+    /// Draws the last shape in a saved coordinate system, instead of the
+    /// current one. For a line, this is the coordinate system of both ends.
+    /// If the last command is not a shape, rendering the scene fails
+    pub fn in_coord_system(&mut self, name: &str) -> &mut Scene {
+        let coord_system_name = Some(name.to_owned());
+        match self.statements.last_mut().map(|statement| &mut statement.command) {
+            Some(Command::Sphere { coord_system, .. }) | Some(Command::Box { coord_system, .. }) | Some(Command::Torus { coord_system, .. }) |
+            Some(Command::Mesh { coord_system, .. }) => *coord_system = coord_system_name,
+            Some(Command::Line { start_coord_system, end_coord_system, .. }) => {
+                *start_coord_system = coord_system_name.clone();
+                *end_coord_system = coord_system_name;
+            }
+            _ => self.diagnostics.general_error(format!("coordinate system {} follows a command that is not a shape", name)),
+        }
+        self
+    }
+
+    /// Draws every frame of the scene, or the frames in options.frame_range.
+    /// Returns: the frames in order along with the warnings about the scene,
+    /// or every diagnostic found in it if it could not be rendered
+    pub fn render(&self, options: &RenderOptions) -> Result<(Vec<Image>, Vec<Diagnostic>), MdlError> {
+        render::render_images("scene", &self.statements, options, self.diagnostics.clone())
+    }
+
+    /// Renders the scene the way a script is rendered, saving its image or
+    /// writing the frames and gif of its animation into options.output_dir
+    /// Returns: the warnings about the scene, or every diagnostic found in it
+    pub fn write(&self, options: &RenderOptions) -> Result<Vec<Diagnostic>, MdlError> {
+        render::render("scene", &self.statements, options, self.diagnostics.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(scene: &Scene) -> Vec<String> {
+        let options = RenderOptions { dry_run: true, ..RenderOptions::default() };
+        scene.render(&options).unwrap_err().diagnostics.into_iter().map(|diagnostic| diagnostic.message).collect()
+    }
+
+    #[test]
+    fn misplaced_modifiers_are_reported_when_rendering() {
+        let mut scene = Scene::new();
+        scene.knob("spin");
+        assert_eq!(errors(&scene), ["knob spin follows a command that cannot be scaled by a knob"]);
+
+        let mut scene = Scene::new();
+        scene.push().with_constants("shiny");
+        assert_eq!(errors(&scene), ["constants shiny follow a command that is not a shape"]);
+
+        let mut scene = Scene::new();
+        scene.translate([1.0, 2.0, 3.0]).in_coord_system("saved");
+        assert_eq!(errors(&scene), ["coordinate system saved follows a command that is not a shape"]);
+    }
+
+    #[test]
+    fn knob_on_a_light_scales_its_location() {
+        let mut scene = Scene::new();
+        scene.add_light("sun", [255.0, 255.0, 255.0], [1.0, 1.0, 1.0]).knob("spin");
+        match &scene.statements[0].command {
+            Command::Light { color_knob, location_knob, .. } => {
+                assert_eq!(*color_knob, None);
+                assert_eq!(location_knob.as_deref(), Some("spin"));
+            }
+            other => panic!("expected a light, got {:?}", other),
+        }
+    }
+}